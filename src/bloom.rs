@@ -0,0 +1,94 @@
+use crate::framebuffer::Framebuffer;
+use nalgebra_glm::Vec3;
+
+const LUMA: Vec3 = Vec3::new(0.2126, 0.7152, 0.0722);
+
+// One level of the blur pyramid, in linear RGB
+struct Level { w: usize, h: usize, data: Vec<Vec3> }
+
+impl Level {
+    fn new(w: usize, h: usize) -> Self { Self { w, h, data: vec![Vec3::zeros(); w * h] } }
+
+    // Bilinear lookup at continuous pixel coordinates (pixel centers at .5), clamped to the edges
+    fn sample(&self, x: f32, y: f32) -> Vec3 {
+        let x = (x - 0.5).clamp(0.0, (self.w - 1) as f32); let y = (y - 0.5).clamp(0.0, (self.h - 1) as f32);
+        let (x0, y0) = (x as usize, y as usize); let (x1, y1) = ((x0 + 1).min(self.w - 1), (y0 + 1).min(self.h - 1));
+        let (fx, fy) = (x - x0 as f32, y - y0 as f32);
+        let row = |y: usize| self.data[y * self.w + x0] * (1.0 - fx) + self.data[y * self.w + x1] * fx;
+        row(y0) * (1.0 - fy) + row(y1) * fy
+    }
+
+    // Separable 5-tap binomial blur, edges clamped
+    fn blur(&mut self, scratch: &mut Vec<Vec3>) {
+        const K: [f32; 5] = [1.0 / 16.0, 4.0 / 16.0, 6.0 / 16.0, 4.0 / 16.0, 1.0 / 16.0];
+        let (w, h) = (self.w as i32, self.h as i32);
+        scratch.resize(self.data.len(), Vec3::zeros());
+        for y in 0..h { for x in 0..w {
+            scratch[(y * w + x) as usize] = (0..5).map(|k| self.data[(y * w + (x + k as i32 - 2).clamp(0, w - 1)) as usize] * K[k]).sum();
+        }}
+        for y in 0..h { for x in 0..w {
+            self.data[(y * w + x) as usize] = (0..5).map(|k| scratch[((y + k as i32 - 2).clamp(0, h - 1) * w + x) as usize] * K[k]).sum();
+        }}
+    }
+}
+
+// Screen-space bloom: pixels brighter than `threshold` (linear luminance, so only emissive surfaces
+// pass) are downsampled into a pyramid, each level is blurred and the levels are summed back up and
+// added to the frame. Because it works on what's visible, anything in front of the Sun hides its glow.
+pub struct Bloom { pub threshold: f32, pub intensity: f32, levels: Vec<Level>, scratch: Vec<Vec3> }
+
+impl Bloom {
+    pub fn new(intensity: f32) -> Self { Self { threshold: 1.0, intensity, levels: Vec::new(), scratch: Vec::new() } }
+
+    pub fn apply(&mut self, fb: &mut Framebuffer) {
+        if self.intensity <= 0.0 { return; }
+        // Half resolution down to ~10 px, at most six levels
+        if self.levels.first().map(|l| (l.w, l.h)) != Some(((fb.width / 2).max(1), (fb.height / 2).max(1))) {
+            self.levels.clear();
+            let (mut w, mut h) = ((fb.width / 2).max(1), (fb.height / 2).max(1));
+            while self.levels.len() < 6 && w.min(h) >= 8 { self.levels.push(Level::new(w, h)); w /= 2; h /= 2; }
+            if self.levels.is_empty() { return; }
+        }
+
+        // Bright pass with a soft cutoff, averaged 2x2 into the first level
+        let threshold = self.threshold;
+        let bright = |c: Vec3| { let l = c.dot(&LUMA); if l <= threshold { Vec3::zeros() } else { c * ((l - threshold) / l) } };
+        let first = &mut self.levels[0];
+        let mut any = false;
+        for y in 0..first.h { for x in 0..first.w {
+            let mut sum = Vec3::zeros();
+            for (dx, dy) in [(0, 0), (1, 0), (0, 1), (1, 1)] {
+                let (sx, sy) = ((2 * x + dx).min(fb.width - 1), (2 * y + dy).min(fb.height - 1));
+                sum += bright(fb.radiance(sy * fb.width + sx));
+            }
+            first.data[y * first.w + x] = sum * 0.25; any |= sum != Vec3::zeros();
+        }}
+        if !any { return; }
+
+        // Downsample; every level keeps the same total energy at a coarser footprint
+        for i in 1..self.levels.len() {
+            let (src, dst) = self.levels.split_at_mut(i); let (src, dst) = (&src[i - 1], &mut dst[0]);
+            for y in 0..dst.h { for x in 0..dst.w {
+                dst.data[y * dst.w + x] = src.sample(2.0 * x as f32 + 1.0, 2.0 * y as f32 + 1.0);
+            }}
+        }
+        for level in &mut self.levels { level.blur(&mut self.scratch); }
+
+        // Upsample and accumulate from the widest level down
+        for i in (1..self.levels.len()).rev() {
+            let (dst, src) = self.levels.split_at_mut(i); let (dst, src) = (&mut dst[i - 1], &src[0]);
+            for y in 0..dst.h { for x in 0..dst.w {
+                let c = src.sample((x as f32 + 0.5) * 0.5, (y as f32 + 0.5) * 0.5);
+                dst.data[y * dst.w + x] += c;
+            }}
+        }
+
+        // Composite: the average over the levels, scaled by `intensity`
+        let first = &self.levels[0]; let k = self.intensity / self.levels.len() as f32;
+        let (sx, sy) = (first.w as f32 / fb.width as f32, first.h as f32 / fb.height as f32);
+        for y in 0..fb.height { for x in 0..fb.width {
+            let c = first.sample((x as f32 + 0.5) * sx, (y as f32 + 0.5) * sy) * k;
+            if c.max() > 1e-4 { fb.add_radiance(y * fb.width + x, c); }
+        }}
+    }
+}
@@ -34,3 +34,26 @@ impl FreeOrbitCamera {
         self.center += forward_vec * forward + right_vec * right + self.up * up;
     }
 }
+
+// Creative camera warp helpers
+pub fn ease_in_out_cubic(t: f32) -> f32 {
+    if t < 0.5 { 4.0 * t * t * t } else { 1.0 - (-2.0 * t + 2.0).powi(3) / 2.0 }
+}
+
+pub fn ease_out_back(t: f32) -> f32 {
+    let c1 = 1.70158f32; let c3 = c1 + 1.0;
+    1.0 + c3 * (t - 1.0).powi(3) + c1 * (t - 1.0).powi(2)
+}
+
+pub fn bezier3(p0: Vec3, c1: Vec3, c2: Vec3, p3: Vec3, t: f32) -> Vec3 {
+    let it = 1.0 - t;
+    let b0 = it*it*it; let b1 = 3.0*it*it*t; let b2 = 3.0*it*t*t; let b3 = t*t*t;
+    p0*b0 + c1*b1 + c2*b2 + p3*b3
+}
+
+pub fn rotate_around_axis(v: Vec3, axis: Vec3, angle: f32) -> Vec3 {
+    let a = axis.normalize();
+    let c = angle.cos(); let s = angle.sin();
+    v*c + a.cross(&v)*s + a*(a.dot(&v))*(1.0 - c)
+}
+
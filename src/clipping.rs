@@ -0,0 +1,115 @@
+use crate::vertex::Vertex;
+use nalgebra_glm::{Mat4, Vec3, Vec4};
+
+// Homogeneous clipping against the view frustum, done between the vertex shader and the perspective divide.
+// Each plane is a signed distance on the clip-space position; a vertex is inside when it is >= 0.
+// Near comes first so no vertex with w <= 0 reaches the other planes.
+const PLANES: [fn(&Vec4) -> f32; 6] = [
+    |p| p.z + p.w, // near
+    |p| p.w - p.z, // far
+    |p| p.w + p.x, // left
+    |p| p.w - p.x, // right
+    |p| p.w + p.y, // bottom
+    |p| p.w - p.y, // top
+];
+
+// Perspective divide and viewport transform of a clip-space position
+pub fn to_screen(clip: &Vec4, viewport: &Mat4) -> Vec3 {
+    let ndc = Vec4::new(clip.x / clip.w, clip.y / clip.w, clip.z / clip.w, 1.0);
+    let screen = viewport * ndc;
+    Vec3::new(screen.x, screen.y, screen.z)
+}
+
+// Part of the segment a -> b (clip-space positions) inside the frustum, as the parameters where it enters and leaves
+pub fn clip_segment(a: &Vec4, b: &Vec4) -> Option<(f32, f32)> {
+    let (mut t0, mut t1) = (0.0f32, 1.0f32);
+    for plane in PLANES {
+        let (da, db) = (plane(a), plane(b));
+        if da < 0.0 && db < 0.0 { return None; }
+        if da < 0.0 { t0 = t0.max(da / (da - db)); } else if db < 0.0 { t1 = t1.min(da / (da - db)); }
+    }
+    (t0 < t1).then_some((t0, t1))
+}
+
+// Vertex at `t` along a -> b, with every attribute interpolated linearly in clip space
+fn lerp_vertex(a: &Vertex, b: &Vertex, t: f32) -> Vertex {
+    Vertex {
+        position: a.position.lerp(&b.position, t),
+        normal: a.normal.lerp(&b.normal, t),
+        tex_coords: a.tex_coords.lerp(&b.tex_coords, t),
+        tangent: a.tangent.lerp(&b.tangent, t),
+        color: a.color * (1.0 - t) + b.color * t,
+        clip_position: a.clip_position.lerp(&b.clip_position, t),
+        transformed_position: a.transformed_position,
+        transformed_normal: a.transformed_normal.lerp(&b.transformed_normal, t),
+        transformed_tangent: a.transformed_tangent.lerp(&b.transformed_tangent, t),
+    }
+}
+
+// Clips the triangle (a, b, c) to the frustum and appends the result to `out` as a fan of screen-space
+// triangles, keeping the original winding. Triangles fully inside are passed through untouched.
+pub fn clip_triangle(a: &Vertex, b: &Vertex, c: &Vertex, viewport: &Mat4, out: &mut Vec<Vertex>) {
+    let d = |v: &Vertex| PLANES.map(|plane| plane(&v.clip_position));
+    let (da, db, dc) = (d(a), d(b), d(c));
+    if (0..PLANES.len()).all(|i| da[i] >= 0.0 && db[i] >= 0.0 && dc[i] >= 0.0) { out.extend([a.clone(), b.clone(), c.clone()]); return; }
+    if (0..PLANES.len()).any(|i| da[i] < 0.0 && db[i] < 0.0 && dc[i] < 0.0) { return; }
+
+    // Sutherland-Hodgman, one plane at a time
+    let mut poly = vec![a.clone(), b.clone(), c.clone()];
+    for plane in PLANES {
+        let mut next = Vec::with_capacity(poly.len() + 1);
+        for i in 0..poly.len() {
+            let (p, q) = (&poly[i], &poly[(i + 1) % poly.len()]);
+            let (dp, dq) = (plane(&p.clip_position), plane(&q.clip_position));
+            if dp >= 0.0 { next.push(p.clone()); }
+            // Always interpolate from the inside end so the edge shared with a neighbour gets the exact same vertex
+            if dp >= 0.0 && dq < 0.0 { next.push(lerp_vertex(p, q, dp / (dp - dq))); }
+            if dp < 0.0 && dq >= 0.0 { next.push(lerp_vertex(q, p, dq / (dq - dp))); }
+        }
+        poly = next;
+        if poly.len() < 3 { return; }
+    }
+
+    for v in poly.iter_mut() { v.transformed_position = to_screen(&v.clip_position, viewport); }
+    for i in 1..poly.len() - 1 { out.extend([poly[0].clone(), poly[i].clone(), poly[i + 1].clone()]); }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use nalgebra_glm::vec3;
+
+    fn vertex(clip: Vec4) -> Vertex { let mut v = Vertex::new(vec3(0.0, 0.0, 0.0), vec3(0.0, 0.0, 1.0)); v.clip_position = clip; v }
+
+    #[test]
+    fn triangle_inside_passes_through() {
+        let (a, b, c) = (vertex(Vec4::new(0.0, 0.0, 0.5, 1.0)), vertex(Vec4::new(0.5, 0.0, 0.5, 1.0)), vertex(Vec4::new(0.0, 0.5, 0.5, 1.0)));
+        let mut out = Vec::new();
+        clip_triangle(&a, &b, &c, &Mat4::identity(), &mut out);
+        assert_eq!(out.len(), 3);
+        assert_eq!(out[1].clip_position, b.clip_position);
+    }
+
+    #[test]
+    fn triangle_behind_the_near_plane_is_dropped() {
+        let (a, b, c) = (vertex(Vec4::new(0.0, 0.0, -2.0, 1.0)), vertex(Vec4::new(0.5, 0.0, -2.0, 1.0)), vertex(Vec4::new(0.0, 0.5, -2.0, 1.0)));
+        let mut out = Vec::new();
+        clip_triangle(&a, &b, &c, &Mat4::identity(), &mut out);
+        assert!(out.is_empty());
+    }
+
+    #[test]
+    fn triangle_crossing_the_near_plane_becomes_a_quad() {
+        // One vertex behind the camera (w < 0): the near plane cuts two edges and leaves a quad, two triangles
+        let (a, b, c) = (vertex(Vec4::new(0.0, 0.0, 0.5, 1.0)), vertex(Vec4::new(0.5, 0.0, 0.5, 1.0)), vertex(Vec4::new(0.0, 0.0, -1.5, -0.5)));
+        let mut out = Vec::new();
+        clip_triangle(&a, &b, &c, &Mat4::identity(), &mut out);
+        assert_eq!(out.len(), 6);
+        for v in &out {
+            let p = v.clip_position;
+            assert!(p.z + p.w >= -1e-5 && p.w > 0.0, "{p:?}");
+        }
+        // The new vertices lie on the near plane, z = -w
+        assert_eq!(out.iter().filter(|v| (v.clip_position.z + v.clip_position.w).abs() < 1e-5).count(), 3);
+    }
+}
@@ -0,0 +1,101 @@
+// Simulation clock, decoupled from the frame rate: advanced by real elapsed seconds times the warp factor
+#[derive(Clone, Copy, Debug)]
+pub struct SimClock {
+    pub seconds: f64,
+    pub speed: f64,
+    pub paused: bool,
+    pub reversed: bool,
+}
+
+pub const MIN_SPEED: f64 = 1.0;
+pub const MAX_SPEED: f64 = 1_000_000.0;
+// Period of the shader animation time: the granulation and clouds jump once per simulated hour
+pub const ANIMATION_PERIOD: f64 = 3600.0;
+
+impl SimClock {
+    pub fn new(seconds: f64) -> Self { Self { seconds, speed: 1.0, paused: false, reversed: false } }
+
+    // Simulated seconds per real second, signed
+    pub fn rate(&self) -> f64 { if self.paused { 0.0 } else if self.reversed { -self.speed } else { self.speed } }
+
+    pub fn advance(&mut self, real_dt: f64) { self.seconds += real_dt * self.rate(); }
+
+    pub fn faster(&mut self) { self.speed = (self.speed * 10.0).min(MAX_SPEED); }
+    pub fn slower(&mut self) { self.speed = (self.speed / 10.0).max(MIN_SPEED); }
+    pub fn toggle_pause(&mut self) { self.paused = !self.paused; }
+    pub fn toggle_reverse(&mut self) { self.reversed = !self.reversed; }
+    pub fn reset_speed(&mut self) { self.speed = 1.0; self.reversed = false; self.paused = false; }
+
+    // Shader animation time in milliseconds, as `Uniforms::time` expects. Wrapped in f64 to `ANIMATION_PERIOD` before
+    // narrowing (like `Scene::spin`), so the f32 noise offsets keep sub-millisecond steps however far the clock has run
+    pub fn millis(&self) -> f32 { (self.seconds.rem_euclid(ANIMATION_PERIOD) * 1000.0) as f32 }
+
+    pub fn status(&self) -> String {
+        let dir = if self.reversed { "-" } else { "" };
+        if self.paused { format!("paused ({dir}x{})", self.speed) } else { format!("{dir}x{}", self.speed) }
+    }
+}
+
+// Ship and asteroid physics run in fixed ticks of `FIXED_DT` real seconds regardless of the frame rate.
+// The accumulator keeps the leftover time; `alpha` is how far the rendered frame sits between the last two ticks.
+pub const FIXED_DT: f32 = 0.016;
+// A frame slower than this many ticks drops the backlog instead of trying to catch up
+const MAX_TICKS: u32 = 8;
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct FixedStep { accumulator: f32 }
+
+impl FixedStep {
+    // Number of ticks to simulate for `real_dt` seconds of wall-clock time
+    pub fn ticks(&mut self, real_dt: f32) -> u32 {
+        self.accumulator += real_dt.max(0.0);
+        // Small epsilon so a frame of exactly FIXED_DT always yields one tick despite rounding
+        let n = ((self.accumulator + 1e-6) / FIXED_DT) as u32;
+        if n > MAX_TICKS { self.accumulator = 0.0; return MAX_TICKS; }
+        self.accumulator = (self.accumulator - n as f32 * FIXED_DT).max(0.0);
+        n
+    }
+
+    pub fn alpha(&self) -> f32 { (self.accumulator / FIXED_DT).clamp(0.0, 1.0) }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn shader_time_keeps_precision_far_into_the_run() {
+        // 30 simulated years in: the raw f32 milliseconds would move in steps of minutes
+        let mut clock = SimClock::new(30.0 * 365.25 * 86400.0 + 12.5);
+        let t0 = clock.millis();
+        clock.advance(0.001);
+        assert!((clock.millis() - t0 - 1.0).abs() < 1e-2, "{} -> {}", t0, clock.millis());
+        assert!((0.0..(ANIMATION_PERIOD * 1000.0) as f32).contains(&t0));
+        assert!(SimClock::new(-10.0).millis() >= 0.0);
+    }
+
+    #[test]
+    fn fixed_step_counts_ticks_and_carries_the_remainder() {
+        let mut step = FixedStep::default();
+        let mut ticks = Vec::new(); let mut alphas = Vec::new();
+        for dt in [0.008, 0.008, 0.024, FIXED_DT, 0.0] { ticks.push(step.ticks(dt)); alphas.push(step.alpha()); }
+        assert_eq!(ticks, [0, 1, 1, 1, 0]);
+        for (a, want) in alphas.iter().zip([0.5, 0.0, 0.5, 0.5, 0.5]) { assert!((a - want).abs() < 1e-3, "{alphas:?}"); }
+    }
+
+    #[test]
+    fn fixed_step_exact_frames_tick_once() {
+        let mut step = FixedStep::default();
+        for _ in 0..1000 { assert_eq!(step.ticks(FIXED_DT), 1); }
+        assert!(step.alpha() < 1e-3);
+    }
+
+    #[test]
+    fn fixed_step_caps_slow_frames_and_drops_the_backlog() {
+        let mut step = FixedStep::default();
+        assert_eq!(step.ticks(1.0), MAX_TICKS);
+        assert_eq!(step.alpha(), 0.0);
+        assert_eq!(step.ticks(FIXED_DT), 1);
+        assert_eq!(step.ticks(-1.0), 0);
+    }
+}
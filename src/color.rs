@@ -15,8 +15,57 @@ impl Color {
         }
     }
     pub fn to_hex(self) -> u32 { ((self.r as u32) << 16) | ((self.g as u32) << 8) | (self.b as u32) }
+    pub fn from_hex(hex: u32) -> Self { Self::new(((hex >> 16) & 0xFF) as u8, ((hex >> 8) & 0xFF) as u8, (hex & 0xFF) as u8) }
+
+    // Colors are stored sRGB-encoded; lighting and accumulation in the HDR buffer happen in linear RGB
+    pub fn to_linear(self) -> Vec3 { let lut = srgb_to_linear_lut(); vec3(lut[self.r as usize], lut[self.g as usize], lut[self.b as usize]) }
+    pub fn from_linear(c: Vec3) -> Self { Self::new(linear_to_srgb(c.x), linear_to_srgb(c.y), linear_to_srgb(c.z)) }
+    pub fn with_alpha(self, a: f32) -> Rgba { Rgba { color: self, a } }
+}
+
+// Fragment output with coverage. Opaque draws ignore `a`; blended ones weigh the color by it (see `framebuffer::Blend`)
+#[derive(Debug, Clone, Copy)]
+pub struct Rgba { pub color: Color, pub a: f32 }
+
+impl From<Color> for Rgba { fn from(color: Color) -> Self { Self { color, a: 1.0 } } }
+
+fn srgb_to_linear_lut() -> &'static [f32; 256] {
+    static LUT: OnceLock<[f32; 256]> = OnceLock::new();
+    LUT.get_or_init(|| std::array::from_fn(|i| { let c = i as f32 / 255.0; if c <= 0.04045 { c / 12.92 } else { ((c + 0.055) / 1.055).powf(2.4) } }))
+}
+
+// Linear [0, 1] to an 8-bit sRGB value, through a 4096-entry table
+pub fn linear_to_srgb(v: f32) -> u8 {
+    const N: usize = 4096;
+    static LUT: OnceLock<Vec<u8>> = OnceLock::new();
+    let lut = LUT.get_or_init(|| (0..N).map(|i| { let c = i as f32 / (N - 1) as f32; let s = if c <= 0.0031308 { c * 12.92 } else { 1.055 * c.powf(1.0 / 2.4) - 0.055 }; (s * 255.0).round() as u8 }).collect());
+    lut[(v.clamp(0.0, 1.0) * (N - 1) as f32).round() as usize]
 }
 
-use std::ops::{Add, Mul};
+use nalgebra_glm::{Vec3, vec3};
+use std::{ops::{Add, Mul}, sync::OnceLock};
 impl Add for Color { type Output = Color; fn add(self, o: Color) -> Color { Color { r: self.r.saturating_add(o.r), g: self.g.saturating_add(o.g), b: self.b.saturating_add(o.b) } } }
 impl Mul<f32> for Color { type Output = Color; fn mul(self, s: f32) -> Color { Color { r: (self.r as f32 * s).clamp(0.0, 255.0) as u8, g: (self.g as f32 * s).clamp(0.0, 255.0) as u8, b: (self.b as f32 * s).clamp(0.0, 255.0) as u8 } } }
+// Per-channel scale, e.g. by the colored light reaching a surface
+impl Mul<Vec3> for Color { type Output = Color; fn mul(self, k: Vec3) -> Color { Color { r: (self.r as f32 * k.x).clamp(0.0, 255.0) as u8, g: (self.g as f32 * k.y).clamp(0.0, 255.0) as u8, b: (self.b as f32 * k.z).clamp(0.0, 255.0) as u8 } } }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn srgb_tables_round_trip() {
+        for i in 0..=255u8 {
+            let c = Color::new(i, i, 255 - i);
+            let back = Color::from_linear(c.to_linear());
+            assert_eq!((back.r, back.g, back.b), (c.r, c.g, c.b));
+        }
+    }
+
+    #[test]
+    fn srgb_tables_follow_the_transfer_curve() {
+        let lin = Color::new(188, 0, 255).to_linear();
+        assert!((lin.x - 0.5029).abs() < 1e-3 && lin.y == 0.0 && lin.z == 1.0);
+        assert_eq!((linear_to_srgb(0.5), linear_to_srgb(-1.0), linear_to_srgb(2.0)), (188, 0, 255));
+    }
+}
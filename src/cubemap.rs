@@ -0,0 +1,45 @@
+use nalgebra_glm::{Vec3, vec3};
+
+// Linear radiance over all directions, stored as six square faces (+X, -X, +Y, -Y, +Z, -Z) of `size` texels
+pub struct Cubemap { pub size: usize, texels: Vec<Vec3> }
+
+impl Cubemap {
+    // Evaluates `f` once at each texel's center direction (normalized), one face per thread
+    pub fn bake(size: usize, f: impl Fn(Vec3) -> Vec3 + Sync) -> Self {
+        let mut texels = vec![Vec3::zeros(); 6 * size * size];
+        let f = &f;
+        std::thread::scope(|s| {
+            for (face, out) in texels.chunks_mut(size * size).enumerate() {
+                s.spawn(move || for (i, t) in out.iter_mut().enumerate() {
+                    let (u, v) = (((i % size) as f32 + 0.5) / size as f32 * 2.0 - 1.0, ((i / size) as f32 + 0.5) / size as f32 * 2.0 - 1.0);
+                    *t = f(Self::direction(face, u, v).normalize());
+                });
+            }
+        });
+        Self { size, texels }
+    }
+
+    // Direction through face coordinates (u, v) in [-1, 1]; `locate` is its inverse
+    fn direction(face: usize, u: f32, v: f32) -> Vec3 {
+        match face { 0 => vec3(1.0, -v, -u), 1 => vec3(-1.0, -v, u), 2 => vec3(u, 1.0, v), 3 => vec3(u, -1.0, -v), 4 => vec3(u, -v, 1.0), _ => vec3(-u, -v, -1.0) }
+    }
+
+    // Face hit by `d` (any length) and the face coordinates where it enters
+    fn locate(d: Vec3) -> (usize, f32, f32) {
+        let a = d.abs();
+        if a.x >= a.y && a.x >= a.z { if d.x > 0.0 { (0, -d.z / a.x, -d.y / a.x) } else { (1, d.z / a.x, -d.y / a.x) } }
+        else if a.y >= a.z { if d.y > 0.0 { (2, d.x / a.y, d.z / a.y) } else { (3, d.x / a.y, -d.z / a.y) } }
+        else if d.z > 0.0 { (4, d.x / a.z, -d.y / a.z) } else { (5, -d.x / a.z, -d.y / a.z) }
+    }
+
+    // Bilinear within the face, clamped at its edges (the baked content is smooth, so the seams don't show)
+    pub fn sample(&self, d: Vec3) -> Vec3 {
+        let (face, u, v) = Self::locate(d);
+        let n = self.size as f32;
+        let (x, y) = (((u + 1.0) * 0.5 * n - 0.5).clamp(0.0, n - 1.0), ((v + 1.0) * 0.5 * n - 0.5).clamp(0.0, n - 1.0));
+        let (x0, y0) = (x as usize, y as usize); let (x1, y1) = ((x0 + 1).min(self.size - 1), (y0 + 1).min(self.size - 1));
+        let (tx, ty) = (x - x0 as f32, y - y0 as f32);
+        let texel = |x: usize, y: usize| self.texels[(face * self.size + y) * self.size + x];
+        texel(x0, y0).lerp(&texel(x1, y0), tx).lerp(&texel(x0, y1).lerp(&texel(x1, y1), tx), ty)
+    }
+}
@@ -0,0 +1,121 @@
+use crate::orbit::Orbit;
+use serde::Deserialize;
+
+pub const J2000: f64 = 2451545.0;
+pub const DAYS_PER_CENTURY: f64 = 36525.0;
+
+// Julian day for a Gregorian calendar date (UTC), `hour` may be fractional
+pub fn julian_day(year: i32, month: u32, day: u32, hour: f64) -> f64 {
+    let (y, m) = if month <= 2 { (year - 1, month + 12) } else { (year, month) };
+    let a = (y as f64 / 100.0).floor();
+    let b = 2.0 - a + (a / 4.0).floor();
+    (365.25 * (y as f64 + 4716.0)).floor() + (30.6001 * (m as f64 + 1.0)).floor() + day as f64 + b - 1524.5 + hour / 24.0
+}
+
+// Inverse of `julian_day`: (year, month, day, hour, minute)
+pub fn calendar_date(jd: f64) -> (i32, u32, u32, u32, u32) {
+    let z = (jd + 0.5).floor(); let f = jd + 0.5 - z;
+    let alpha = ((z - 1867216.25) / 36524.25).floor();
+    let a = z + 1.0 + alpha - (alpha / 4.0).floor();
+    let b = a + 1524.0; let c = ((b - 122.1) / 365.25).floor(); let d = (365.25 * c).floor(); let e = ((b - d) / 30.6001).floor();
+    let day = (b - d - (30.6001 * e).floor()) as u32;
+    let month = if e < 14.0 { e - 1.0 } else { e - 13.0 } as u32;
+    let year = if month > 2 { c - 4716.0 } else { c - 4715.0 } as i32;
+    let minutes = (f * 1440.0).round() as u32;
+    // Rounding can push 23:59.99 to 24:00; clamp instead of rolling the date over
+    let minutes = minutes.min(1439);
+    (year, month, day, minutes / 60, minutes % 60)
+}
+
+// Days in `month` of `year` in the proleptic Gregorian calendar (astronomical year numbering, so year 0 is a leap year)
+fn days_in_month(year: i32, month: u32) -> u32 {
+    let leap = year.rem_euclid(4) == 0 && (year.rem_euclid(100) != 0 || year.rem_euclid(400) == 0);
+    match month { 2 if leap => 29, 2 => 28, 4 | 6 | 9 | 11 => 30, _ => 31 }
+}
+
+// Parses "YYYY-MM-DD" or "YYYY-MM-DDTHH:MM" (UTC) into a Julian day
+pub fn parse_date(s: &str) -> Result<f64, String> {
+    let err = || format!("invalid date {s:?}, expected YYYY-MM-DD or YYYY-MM-DDTHH:MM");
+    let (date, time) = match s.split_once(['T', ' ']) { Some((d, t)) => (d, Some(t)), None => (s, None) };
+    // Leading '-' is allowed for years before 1 CE
+    let (sign, date) = match date.strip_prefix('-') { Some(rest) => (-1, rest), None => (1, date) };
+    let parts: Vec<&str> = date.split('-').collect();
+    if parts.len() != 3 { return Err(err()); }
+    let year: i32 = parts[0].parse::<i32>().map_err(|_| err())? * sign;
+    let month: u32 = parts[1].parse().map_err(|_| err())?;
+    let day: u32 = parts[2].parse().map_err(|_| err())?;
+    if !(1..=12).contains(&month) || !(1..=days_in_month(year, month)).contains(&day) { return Err(err()); }
+    let hour = match time {
+        Some(t) => {
+            let (h, m) = t.split_once(':').ok_or_else(err)?;
+            let h: u32 = h.parse().map_err(|_| err())?; let m: u32 = m.parse().map_err(|_| err())?;
+            if h > 23 || m > 59 { return Err(err()); }
+            h as f64 + m as f64 / 60.0
+        }
+        None => 0.0,
+    };
+    Ok(julian_day(year, month, day, hour))
+}
+
+pub fn format_date(jd: f64) -> String {
+    let (y, mo, d, h, mi) = calendar_date(jd);
+    format!("{y:04}-{mo:02}-{d:02} {h:02}:{mi:02} UTC")
+}
+
+// Mean orbital elements at J2000 with their rates per Julian century, as published by JPL
+// ("Keplerian Elements for Approximate Positions of the Major Planets"). Each entry is
+// [value, rate]; angles are in degrees.
+#[derive(Deserialize, Clone, Copy, Debug)]
+pub struct MeanElements {
+    pub e: [f64; 2],
+    pub i: [f64; 2],
+    pub l: [f64; 2],
+    pub w_bar: [f64; 2],
+    pub node: [f64; 2],
+}
+
+impl MeanElements {
+    // Osculating orbit at `jd`; the semi-major axis is the (compressed) display one from the system file
+    pub fn orbit_at(&self, jd: f64, semi_major_axis: f32) -> Orbit {
+        let t = (jd - J2000) / DAYS_PER_CENTURY;
+        let at = |el: [f64; 2]| el[0] + el[1] * t;
+        let (e, i, l, w_bar, node) = (at(self.e), at(self.i), at(self.l), at(self.w_bar), at(self.node));
+        let mean_anomaly = (l - w_bar).rem_euclid(360.0);
+        let arg_periapsis = (w_bar - node).rem_euclid(360.0);
+        // Mean motion in radians per day, used only when stepping along the orbit (trails)
+        let mean_motion = (self.l[1] / DAYS_PER_CENTURY).to_radians();
+        Orbit {
+            semi_major_axis, eccentricity: e.clamp(0.0, 0.99) as f32,
+            inclination: i.to_radians() as f32, ascending_node: node.rem_euclid(360.0).to_radians() as f32, arg_periapsis: arg_periapsis.to_radians() as f32,
+            mean_anomaly: mean_anomaly.to_radians() as f32, mean_motion: mean_motion as f32,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn j2000_is_noon_on_2000_01_01() {
+        assert_eq!(julian_day(2000, 1, 1, 12.0), J2000);
+        assert_eq!(calendar_date(J2000), (2000, 1, 1, 12, 0));
+    }
+
+    #[test]
+    fn julian_day_round_trips_through_calendar_date() {
+        for (y, mo, d, h, mi) in [(2026, 10, 18, 6, 30), (1999, 12, 31, 23, 59), (2024, 2, 29, 0, 0), (1900, 3, 1, 12, 0), (1600, 1, 1, 0, 1), (-500, 6, 15, 18, 45)] {
+            let jd = julian_day(y, mo, d, h as f64 + mi as f64 / 60.0);
+            assert_eq!(calendar_date(jd), (y, mo, d, h, mi));
+        }
+    }
+
+    #[test]
+    fn parse_date_checks_the_month_length() {
+        assert_eq!(parse_date("2024-02-29"), Ok(julian_day(2024, 2, 29, 0.0)));
+        assert_eq!(parse_date("2000-02-29T06:00"), Ok(julian_day(2000, 2, 29, 6.0)));
+        for bad in ["2026-02-31", "2026-04-31", "2023-02-29", "1900-02-29", "2026-13-01", "2026-01-00", "2026-01-01T24:00"] {
+            assert!(parse_date(bad).is_err(), "{bad}");
+        }
+    }
+}
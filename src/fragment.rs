@@ -8,7 +8,10 @@ pub struct Fragment {
     pub normal: Vec3,
     pub intensity: f32,
     pub vertex_position: Vec3,
+    pub tex_coords: Vec2,
+    // World-space tangent (see `Vertex::tangent`)
+    pub tangent: Vec3,
     pub color: Color,
 }
 
-impl Fragment { pub fn new(position: Vec2, depth: f32, normal: Vec3, intensity: f32, vertex_position: Vec3) -> Self { Self { position, depth, normal, intensity, vertex_position, color: Color::new(0,0,0) } } }
+impl Fragment { pub fn new(position: Vec2, depth: f32, normal: Vec3, intensity: f32, vertex_position: Vec3, tex_coords: Vec2, tangent: Vec3) -> Self { Self { position, depth, normal, intensity, vertex_position, tex_coords, tangent, color: Color::new(0,0,0) } } }
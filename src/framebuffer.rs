@@ -1,31 +1,348 @@
+use crate::{color::{Color, Rgba}, tonemap::Tonemap, triangle::{Coverage, Rect}};
+use image::{ImageBuffer, Rgb};
+use nalgebra_glm::Vec3;
+
+// How a draw combines with the framebuffer. `Replace` writes color and depth and ignores alpha; the others
+// depth-test but leave depth alone, so they go after the opaque geometry, back to front. `Alpha` covers the
+// pixel by `a`, `Additive` adds the light on top (atmospheres) and `Multiply` filters what's behind.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Blend { #[default] Replace, Alpha, Additive, Multiply }
+
+impl Blend {
+    // Linear-light result of drawing `src` with coverage `a` over `dst`
+    #[inline]
+    pub fn apply(self, dst: Vec3, src: Vec3, a: f32) -> Vec3 {
+        match self {
+            Blend::Replace => src,
+            Blend::Alpha => dst + (src - dst) * a,
+            Blend::Additive => dst + src * a,
+            Blend::Multiply => dst.component_mul(&(Vec3::repeat(1.0 - a) + src * a)),
+        }
+    }
+}
+
+// Most coverage samples per pixel
+pub const MAX_SAMPLES: usize = 8;
+
+// Sample positions within a pixel for 1, 2, 4 and 8 samples (the usual rotated-grid patterns, in pixel units).
+// Any other count has no pattern and panics
+pub fn sample_offsets(samples: usize) -> &'static [(f32, f32)] {
+    const S1: [(f32, f32); 1] = [(0.5, 0.5)];
+    const S2: [(f32, f32); 2] = [(0.75, 0.75), (0.25, 0.25)];
+    const S4: [(f32, f32); 4] = [(0.375, 0.125), (0.875, 0.375), (0.125, 0.625), (0.625, 0.875)];
+    const S8: [(f32, f32); 8] = [(0.5625, 0.3125), (0.4375, 0.6875), (0.8125, 0.5625), (0.3125, 0.1875), (0.1875, 0.8125), (0.0625, 0.4375), (0.6875, 0.9375), (0.9375, 0.0625)];
+    match samples { 1 => &S1, 2 => &S2, 4 => &S4, 8 => &S8, n => panic!("unsupported sample count {n}, expected 1, 2, 4 or 8") }
+}
+
+// Blends `c` into sample slot `idx`; shared by `Framebuffer` and `Tile`, which handle the depth test themselves.
+// In HDR mode `buffer` is unused, in LDR mode (one sample per pixel) blending goes through linear RGB and back
+#[inline]
+#[allow(clippy::too_many_arguments)]
+fn blend_pixel(hdr: &mut Option<Vec<Vec3>>, buffer: &mut [u32], emissive: &mut [f32], idx: usize, blend: Blend, emission: f32, c: Rgba) {
+    if blend == Blend::Replace {
+        emissive[idx] = emission;
+        match hdr { Some(hdr) => hdr[idx] = c.color.to_linear() * (1.0 + emission), None => buffer[idx] = c.color.to_hex() }
+        return;
+    }
+    if c.a <= 0.0 { return; }
+    if blend == Blend::Alpha { emissive[idx] += (emission - emissive[idx]) * c.a; }
+    match hdr {
+        Some(hdr) => hdr[idx] = blend.apply(hdr[idx], c.color.to_linear() * (1.0 + emission), c.a),
+        None => buffer[idx] = Color::from_linear(blend.apply(Color::from_hex(buffer[idx]).to_linear(), c.color.to_linear(), c.a)).to_hex(),
+    }
+}
+
 pub struct Framebuffer {
     pub width: usize,
     pub height: usize,
     pub buffer: Vec<u32>,
+    // Coverage samples per pixel. `zbuffer`, `hdr` and `emissive` hold `samples` slots per pixel, stored
+    // together; `buffer` always has one entry per pixel
+    pub samples: usize,
     pub zbuffer: Vec<f32>,
+    // Linear RGB accumulation buffer, present in HDR mode and with multisampling. Every write lands here instead
+    // of `buffer`, which only holds the tonemapped, sRGB-encoded result after `resolve`
+    pub hdr: Option<Vec<Vec3>>,
+    // Emission strength of the surface visible at each sample; its radiance is color * (1 + emission)
+    pub emissive: Vec<f32>,
+    pub tonemap: Tonemap,
+    pub exposure: f32,
     current_color: u32,
+    emission: f32,
+    blend: Blend,
 }
 
 impl Framebuffer {
-    pub fn new(width: usize, height: usize) -> Self {
-        Self { width, height, buffer: vec![0; width*height], zbuffer: vec![f32::INFINITY; width*height], current_color: 0x000000 }
+    pub fn new(width: usize, height: usize) -> Self { Self::with_samples(width, height, 1) }
+    // Multisampled framebuffer (`samples` is 1, 2, 4 or 8): triangles are tested and depth-buffered per sample
+    // but shaded once per pixel. Samples are kept in linear RGB, so without `enable_hdr` they resolve with a clamp.
+    // Panics on any other count, so the slots per pixel always match `sample_offsets`
+    pub fn with_samples(width: usize, height: usize, samples: usize) -> Self {
+        assert!(matches!(samples, 1 | 2 | 4 | 8), "unsupported sample count {samples}, expected 1, 2, 4 or 8");
+        let slots = width * height * samples;
+        let hdr = (samples > 1).then(|| vec![Vec3::zeros(); slots]);
+        Self { width, height, buffer: vec![0; width*height], samples, zbuffer: vec![f32::INFINITY; slots], hdr, emissive: vec![0.0; slots], tonemap: if samples > 1 { Tonemap::Clamp } else { Tonemap::Aces }, exposure: 1.0, current_color: 0x000000, emission: 0.0, blend: Blend::Replace }
+    }
+    pub fn enable_hdr(&mut self, tonemap: Tonemap, exposure: f32) {
+        self.hdr = Some(vec![Vec3::zeros(); self.width*self.height*self.samples]); self.tonemap = tonemap; self.exposure = exposure;
     }
+    pub fn sample_offsets(&self) -> &'static [(f32, f32)] { sample_offsets(self.samples) }
     pub fn clear(&mut self, color: u32) {
-        self.buffer.fill(color); self.zbuffer.fill(f32::INFINITY);
+        self.buffer.fill(color); self.zbuffer.fill(f32::INFINITY); self.emissive.fill(0.0);
+        if let Some(hdr) = &mut self.hdr { hdr.fill(Color::from_hex(color).to_linear()); }
     }
     pub fn set_current_color(&mut self, color: u32) { self.current_color = color; }
+    // Emission of everything drawn from now on (0 for lit surfaces); bloom picks up emissive pixels
+    pub fn set_emission(&mut self, emission: f32) { self.emission = emission; }
+    pub fn set_blend(&mut self, blend: Blend) { self.blend = blend; }
+    #[inline]
+    fn store(&mut self, slot: usize) {
+        blend_pixel(&mut self.hdr, &mut self.buffer, &mut self.emissive, slot, self.blend, self.emission, Color::from_hex(self.current_color).into());
+    }
+    // Linear radiance at pixel `idx` (the average of its samples), including emission
+    #[inline]
+    pub fn radiance(&self, idx: usize) -> Vec3 {
+        match &self.hdr {
+            Some(hdr) => hdr[idx * self.samples..(idx + 1) * self.samples].iter().sum::<Vec3>() / self.samples as f32,
+            None => Color::from_hex(self.buffer[idx]).to_linear() * (1.0 + self.emissive[idx]),
+        }
+    }
+    // Adds linear light to every sample of pixel `idx`; the 8-bit buffer saturates
+    #[inline]
+    pub fn add_radiance(&mut self, idx: usize, c: Vec3) {
+        match &mut self.hdr {
+            Some(hdr) => for v in &mut hdr[idx * self.samples..(idx + 1) * self.samples] { *v += c; },
+            None => self.buffer[idx] = Color::from_linear(Color::from_hex(self.buffer[idx]).to_linear() + c).to_hex(),
+        }
+    }
+    // Points cover the whole pixel, so every sample is tested and written
     #[inline]
     pub fn point(&mut self, x: i32, y: i32, depth: f32) {
         if x < 0 || y < 0 || x as usize >= self.width || y as usize >= self.height { return; }
         let idx = y as usize * self.width + x as usize;
-        if depth < self.zbuffer[idx] { self.zbuffer[idx] = depth; self.buffer[idx] = self.current_color; }
+        for slot in idx * self.samples..(idx + 1) * self.samples {
+            if depth < self.zbuffer[slot] { if self.blend == Blend::Replace { self.zbuffer[slot] = depth; } self.store(slot); }
+        }
     }
     #[inline]
     pub fn point_no_depth(&mut self, x: i32, y: i32) {
         if x < 0 || y < 0 || x as usize >= self.width || y as usize >= self.height { return; }
-        let idx = y as usize * self.width + x as usize; self.buffer[idx] = self.current_color;
+        let idx = y as usize * self.width + x as usize;
+        for slot in idx * self.samples..(idx + 1) * self.samples { self.store(slot); }
+    }
+    // Tonemaps and sRGB-encodes the HDR buffer into `buffer`; a no-op in LDR mode. Samples are tonemapped before
+    // they're averaged so a bright surface doesn't swallow the edge next to it
+    pub fn resolve(&mut self) {
+        let Some(hdr) = &self.hdr else { return };
+        let k = 1.0 / self.samples as f32;
+        for (out, px) in self.buffer.iter_mut().zip(hdr.chunks_exact(self.samples)) {
+            let c = px.iter().map(|c| self.tonemap.apply(*c, self.exposure)).sum::<Vec3>() * k;
+            *out = Color::from_linear(c).to_hex();
+        }
+    }
+    // Anti-aliased line between screen-space points (z is depth, interpolated along the line), `width` pixels
+    // wide. A pixel's coverage comes from its distance to the segment; it's depth-tested against the scene but
+    // doesn't write depth, and blends with `Alpha` unless another blended mode is set. Without a cap the pixels
+    // past that end are left to the next segment, so polylines don't draw their joints twice
+    pub fn draw_line_aa(&mut self, p0: Vec3, p1: Vec3, width: f32, start_cap: bool, end_cap: bool) {
+        if !(p0.x.is_finite() && p0.y.is_finite() && p1.x.is_finite() && p1.y.is_finite()) { return; }
+        // Lines thinner than a pixel keep a pixel's footprint and fade instead
+        let (hw, fade) = (width.max(1.0) * 0.5, width.clamp(0.0, 1.0));
+        let blend = if self.blend == Blend::Replace { Blend::Alpha } else { self.blend };
+        let color = Color::from_hex(self.current_color);
+        let d = p1 - p0; let len2 = d.x * d.x + d.y * d.y;
+        // Walk the major axis one pixel at a time and cover the span across it
+        let steep = d.y.abs() > d.x.abs();
+        let (a0, a1, limit) = if steep { (p0.y, p1.y, self.height) } else { (p0.x, p1.x, self.width) };
+        let lo = ((a0.min(a1) - hw - 1.0).floor() as i32).max(0); let hi = ((a0.max(a1) + hw + 1.0).ceil() as i32).min(limit as i32 - 1);
+        let reach = (hw + 1.0) * std::f32::consts::SQRT_2;
+        for m in lo..=hi {
+            let s = if (a1 - a0).abs() > 1e-6 { ((m as f32 + 0.5 - a0) / (a1 - a0)).clamp(0.0, 1.0) } else { 0.0 };
+            let c = if steep { p0.x + d.x * s } else { p0.y + d.y * s };
+            for n in (c - reach).floor() as i32..=(c + reach).ceil() as i32 {
+                let (x, y) = if steep { (n, m) } else { (m, n) };
+                if x < 0 || y < 0 || x as usize >= self.width || y as usize >= self.height { continue; }
+                let (px, py) = (x as f32 + 0.5 - p0.x, y as f32 + 0.5 - p0.y);
+                let t = if len2 > 1e-12 { (px * d.x + py * d.y) / len2 } else { 0.0 };
+                if (!start_cap && t < 0.0) || (!end_cap && t >= 1.0) { continue; }
+                let t = t.clamp(0.0, 1.0);
+                let dist = ((px - d.x * t).powi(2) + (py - d.y * t).powi(2)).sqrt();
+                let cov = (hw + 0.5 - dist).clamp(0.0, 1.0) * fade;
+                if cov <= 0.0 { continue; }
+                let depth = p0.z + d.z * t;
+                let idx = y as usize * self.width + x as usize;
+                for slot in idx * self.samples..(idx + 1) * self.samples {
+                    if depth < self.zbuffer[slot] { blend_pixel(&mut self.hdr, &mut self.buffer, &mut self.emissive, slot, blend, self.emission, color.with_alpha(cov)); }
+                }
+            }
+        }
     }
-    pub fn draw_line(&mut self, x0: i32, y0: i32, x1: i32, y1: i32) {
-        let mut x0 = x0; let mut y0 = y0; let dx = (x1 - x0).abs(); let sx = if x0 < x1 {1} else {-1}; let dy = -(y1 - y0).abs(); let sy = if y0 < y1 {1} else {-1}; let mut err = dx + dy; loop { self.point_no_depth(x0, y0); if x0 == x1 && y0 == y1 { break; } let e2 = 2*err; if e2 >= dy { err += dy; x0 += sx; } if e2 <= dx { err += dx; y0 += sy; } }
+    pub fn save_png(&self, path: &str) -> Result<(), image::ImageError> {
+        let mut img = ImageBuffer::<Rgb<u8>, Vec<u8>>::new(self.width as u32, self.height as u32);
+        for y in 0..self.height { for x in 0..self.width { let px = self.buffer[y*self.width + x]; let r=((px>>16)&0xFF) as u8; let g=((px>>8)&0xFF) as u8; let b=(px&0xFF) as u8; img.put_pixel(x as u32, y as u32, Rgb([r,g,b])); } }
+        img.save(path)
+    }
+}
+
+// Copy of a rectangle of the framebuffer that one worker thread rasterizes into on its own;
+// coordinates stay in framebuffer space
+pub struct Tile {
+    pub rect: Rect,
+    pub buffer: Vec<u32>,
+    pub samples: usize,
+    pub zbuffer: Vec<f32>,
+    // Linear colors in HDR mode, in which case `buffer` is left empty
+    pub hdr: Option<Vec<Vec3>>,
+    pub emissive: Vec<f32>,
+    emission: f32,
+    blend: Blend,
+}
+
+impl Tile {
+    fn width(&self) -> usize { (self.rect.x1 - self.rect.x0 + 1) as usize }
+    #[inline]
+    fn index(&self, x: i32, y: i32) -> usize { (y - self.rect.y0) as usize * self.width() + (x - self.rect.x0) as usize }
+    // Samples of `cov` at (x, y) in front of what's already there, as a mask; (x, y) must lie inside `rect`
+    #[inline]
+    pub fn visible(&self, x: i32, y: i32, cov: &Coverage) -> u8 {
+        let base = self.index(x, y) * self.samples;
+        (0..self.samples).filter(|&s| cov.mask & (1 << s) != 0 && cov.depth[s] < self.zbuffer[base + s]).fold(0, |m, s| m | 1 << s)
+    }
+    // Writes the fragment's color to the samples in `mask` (from `visible`), same rules as `Framebuffer::point`
+    #[inline]
+    pub fn point(&mut self, x: i32, y: i32, mask: u8, cov: &Coverage, c: Rgba) {
+        let base = self.index(x, y) * self.samples;
+        for s in (0..self.samples).filter(|s| mask & (1 << s) != 0) {
+            if self.blend == Blend::Replace { self.zbuffer[base + s] = cov.depth[s]; }
+            blend_pixel(&mut self.hdr, &mut self.buffer, &mut self.emissive, base + s, self.blend, self.emission, c);
+        }
+    }
+}
+
+impl Framebuffer {
+    pub fn read_tile(&self, rect: Rect) -> Tile {
+        let s = self.samples;
+        let (x0, x1) = (rect.x0 as usize, rect.x1 as usize + 1);
+        let mut tile = Tile { rect, buffer: Vec::new(), samples: s, zbuffer: Vec::new(), hdr: self.hdr.as_ref().map(|_| Vec::new()), emissive: Vec::new(), emission: self.emission, blend: self.blend };
+        for y in rect.y0 as usize..=rect.y1 as usize {
+            let row = y * self.width;
+            let slots = (row + x0) * s..(row + x1) * s;
+            match (&mut tile.hdr, &self.hdr) {
+                (Some(dst), Some(src)) => dst.extend_from_slice(&src[slots.clone()]),
+                _ => tile.buffer.extend_from_slice(&self.buffer[row + x0..row + x1]),
+            }
+            tile.zbuffer.extend_from_slice(&self.zbuffer[slots.clone()]);
+            tile.emissive.extend_from_slice(&self.emissive[slots]);
+        }
+        tile
+    }
+    pub fn write_tile(&mut self, tile: &Tile) {
+        let (x0, w, s) = (tile.rect.x0 as usize, tile.width(), self.samples);
+        for (i, y) in (tile.rect.y0 as usize..=tile.rect.y1 as usize).enumerate() {
+            let row = y * self.width + x0;
+            let (dst, src) = (row * s..(row + w) * s, i * w * s..(i + 1) * w * s);
+            match (&mut self.hdr, &tile.hdr) {
+                (Some(hdr), Some(t)) => hdr[dst.clone()].copy_from_slice(&t[src.clone()]),
+                _ => self.buffer[row..row + w].copy_from_slice(&tile.buffer[i * w..(i + 1) * w]),
+            }
+            self.zbuffer[dst.clone()].copy_from_slice(&tile.zbuffer[src.clone()]);
+            self.emissive[dst].copy_from_slice(&tile.emissive[src]);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sample_slots_match_the_offset_table() {
+        for n in [1, 2, 4, 8] {
+            let fb = Framebuffer::with_samples(3, 2, n);
+            assert_eq!(fb.sample_offsets().len(), n);
+            assert_eq!(fb.zbuffer.len(), 3 * 2 * n);
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "unsupported sample count 3")]
+    fn unsupported_sample_count_panics() { Framebuffer::with_samples(3, 2, 3); }
+
+    #[test]
+    fn resolve_tonemaps_then_averages_the_samples() {
+        let mut fb = Framebuffer::with_samples(2, 1, 4);
+        fb.hdr = Some(vec![Vec3::repeat(1.0), Vec3::repeat(1.0), Vec3::zeros(), Vec3::zeros(), Vec3::repeat(10.0), Vec3::zeros(), Vec3::zeros(), Vec3::zeros()]);
+        fb.resolve();
+        // Half-covered white is half the linear light; a bright sample is clamped before it's averaged in
+        assert_eq!(fb.buffer[0], Color::from_linear(Vec3::repeat(0.5)).to_hex());
+        assert_eq!(fb.buffer[1], Color::from_linear(Vec3::repeat(0.25)).to_hex());
+    }
+
+    #[test]
+    fn points_cover_every_sample() {
+        let mut fb = Framebuffer::with_samples(2, 2, 8);
+        fb.set_current_color(0xffffff);
+        fb.point(1, 0, 0.5);
+        fb.resolve();
+        assert_eq!(fb.buffer, vec![0, 0xffffff, 0, 0]);
+        assert!(fb.zbuffer[8..16].iter().all(|&z| z == 0.5) && fb.zbuffer[..8].iter().all(|z| z.is_infinite()));
+    }
+
+    #[test]
+    fn lines_are_depth_tested_but_write_no_depth() {
+        let mut fb = Framebuffer::new(10, 5);
+        for y in 0..5 { for x in 0..5 { fb.zbuffer[y * 10 + x] = 0.2; } }
+        fb.set_current_color(0xffffff);
+        fb.draw_line_aa(Vec3::new(0.0, 2.5, 0.5), Vec3::new(10.0, 2.5, 0.5), 1.0, true, true);
+        // Hidden behind the left half, drawn over the empty right half
+        assert_eq!(&fb.buffer[20..30], &[0, 0, 0, 0, 0, 0xffffff, 0xffffff, 0xffffff, 0xffffff, 0xffffff]);
+        assert!(fb.zbuffer[20..25].iter().all(|&z| z == 0.2) && fb.zbuffer[25..30].iter().all(|z| z.is_infinite()));
+        fb.draw_line_aa(Vec3::new(0.0, 2.5, 0.1), Vec3::new(10.0, 2.5, 0.1), 1.0, true, true);
+        assert!(fb.buffer[20..30].iter().all(|&c| c == 0xffffff));
+    }
+
+    #[test]
+    fn polyline_joints_are_drawn_once() {
+        // Half-covered segments: a joint blended twice would come out brighter than the rest of the line
+        let mut fb = Framebuffer::new(10, 5);
+        fb.set_current_color(0xffffff);
+        fb.draw_line_aa(Vec3::new(0.0, 2.5, 0.0), Vec3::new(5.0, 2.5, 0.0), 0.5, true, false);
+        fb.draw_line_aa(Vec3::new(5.0, 2.5, 0.0), Vec3::new(10.0, 2.5, 0.0), 0.5, false, true);
+        assert!(fb.buffer[20..30].iter().all(|&c| c == 0xbcbcbc), "{:x?}", &fb.buffer[20..30]);
+    }
+
+    // One HDR slot holding `dst` with emission 1, after blending `c` into it
+    fn blend_hdr(dst: Vec3, blend: Blend, emission: f32, c: Rgba) -> (Vec3, f32) {
+        let (mut hdr, mut emissive) = (Some(vec![dst]), vec![1.0]);
+        blend_pixel(&mut hdr, &mut [], &mut emissive, 0, blend, emission, c);
+        (hdr.unwrap()[0], emissive[0])
+    }
+
+    fn close(a: Vec3, b: Vec3) -> bool { (a - b).magnitude() < 1e-5 }
+
+    #[test]
+    fn blend_modes_in_linear_light() {
+        let dst = nalgebra_glm::vec3(0.2, 0.4, 0.8);
+        let c = Color::new(255, 188, 0);
+        let src = c.to_linear();
+        let (v, e) = blend_hdr(dst, Blend::Replace, 0.5, c.with_alpha(0.1));
+        assert!(close(v, src * 1.5) && e == 0.5, "replace ignores alpha and scales by the emission");
+        let (v, e) = blend_hdr(dst, Blend::Alpha, 0.0, c.with_alpha(0.25));
+        assert!(close(v, dst * 0.75 + src * 0.25) && e == 0.75);
+        let (v, e) = blend_hdr(dst, Blend::Additive, 0.0, c.with_alpha(0.5));
+        assert!(close(v, dst + src * 0.5) && e == 1.0);
+        let (v, _) = blend_hdr(dst, Blend::Multiply, 0.0, c.with_alpha(0.5));
+        assert!(close(v, dst.component_mul(&(Vec3::repeat(0.5) + src * 0.5))));
+        for blend in [Blend::Alpha, Blend::Additive, Blend::Multiply] { assert_eq!(blend_hdr(dst, blend, 2.0, c.with_alpha(0.0)), (dst, 1.0), "{blend:?}"); }
+    }
+
+    #[test]
+    fn blend_modes_in_the_8_bit_buffer() {
+        let blend_ldr = |dst: u32, blend: Blend, c: Rgba| { let mut buffer = [dst]; blend_pixel(&mut None, &mut buffer, &mut [0.0], 0, blend, 0.0, c); buffer[0] };
+        // Half-covered white over black is half the linear light, 188 once encoded
+        assert_eq!(blend_ldr(0x000000, Blend::Alpha, Color::new(255, 255, 255).with_alpha(0.5)), 0xbcbcbc);
+        assert_eq!(blend_ldr(0x123456, Blend::Replace, Color::new(1, 2, 3).with_alpha(0.0)), 0x010203);
+        assert_eq!(blend_ldr(0x808080, Blend::Additive, Color::new(255, 0, 0).with_alpha(1.0)), 0xff8080);
+        assert_eq!(blend_ldr(0xffffff, Blend::Multiply, Color::new(255, 0, 0).with_alpha(1.0)), 0xff0000);
     }
 }
@@ -0,0 +1,90 @@
+use crate::{cubemap::Cubemap, shaders::smoothstep, skybox::equatorial_to_world};
+use fastnoise_lite::{FastNoiseLite, FractalType, NoiseType};
+use nalgebra_glm::{Vec3, vec3};
+use serde::Deserialize;
+
+// Galactic north pole and center (J2000 RA hours, Dec degrees), so the band lies where it is behind the catalog stars
+const NORTH_POLE: (f32, f32) = (12.8567, 27.1283);
+const CENTER: (f32, f32) = (17.7611, -28.9362);
+// Radiance of the band and of a nebula's core at brightness 1: faint next to the stars, like the real sky
+const BAND_RADIANCE: f32 = 0.03;
+const NEBULA_RADIANCE: f32 = 0.06;
+
+// Milky Way background, the `[milky_way]` table of the sky file. `width` is the band's half-width and `nebula_size`
+// the typical nebula radius, both in degrees; `bulge` brightens the galactic center, `dust` sets how dark the lanes
+// along the plane get, `frequency` the scale of the star clouds and `resolution` the texels per cubemap face
+#[derive(Deserialize, Clone, Copy, Debug)]
+pub struct MilkyWayDesc {
+    pub seed: i32,
+    #[serde(default = "default_resolution")] pub resolution: usize,
+    #[serde(default = "default_one")] pub brightness: f32,
+    #[serde(default = "default_width")] pub width: f32,
+    #[serde(default = "default_bulge")] pub bulge: f32,
+    #[serde(default = "default_dust")] pub dust: f32,
+    #[serde(default = "default_frequency")] pub frequency: f32,
+    #[serde(default = "default_nebulae")] pub nebulae: usize,
+    #[serde(default = "default_nebula_size")] pub nebula_size: f32,
+    #[serde(default = "default_one")] pub nebula_brightness: f32,
+}
+
+fn default_resolution() -> usize { 256 }
+fn default_one() -> f32 { 1.0 }
+fn default_width() -> f32 { 10.0 }
+fn default_bulge() -> f32 { 1.2 }
+fn default_dust() -> f32 { 0.7 }
+fn default_frequency() -> f32 { 2.5 }
+fn default_nebulae() -> usize { 14 }
+fn default_nebula_size() -> f32 { 4.0 }
+
+fn noise(seed: i32, freq: f32, octaves: i32) -> FastNoiseLite {
+    let mut n = FastNoiseLite::with_seed(seed);
+    n.set_noise_type(Some(NoiseType::OpenSimplex2)); n.set_fractal_type(Some(FractalType::FBm)); n.set_fractal_octaves(Some(octaves)); n.set_frequency(Some(freq));
+    n
+}
+
+// Emission nebula: center direction, angular radius and color
+struct Nebula { dir: Vec3, radius: f32, color: Vec3 }
+
+// Paints the galactic band (mottled star clouds and a bright bulge), the dust lanes that split it and a few colored
+// nebulae along the plane, and bakes them into a cubemap so the sky costs one lookup per pixel afterwards
+pub fn bake(desc: &MilkyWayDesc) -> Result<Cubemap, Box<dyn std::error::Error>> {
+    if desc.resolution < 2 { return Err(format!("milky_way: resolution {} is too small", desc.resolution).into()); }
+    // Galactic frame: `pole` is latitude +90, `center` longitude 0 and `side` longitude 90
+    let pole = equatorial_to_world(NORTH_POLE.0, NORTH_POLE.1);
+    let center = equatorial_to_world(CENTER.0, CENTER.1);
+    let center = (center - pole * pole.dot(&center)).normalize();
+    let side = pole.cross(&center);
+    let (clouds, dust, wisps) = (noise(desc.seed, desc.frequency, 6), noise(desc.seed + 1, desc.frequency * 1.6, 5), noise(desc.seed + 2, desc.frequency * 4.0, 4));
+    let width = desc.width.to_radians().max(1e-3);
+
+    // Nebulae sit near the plane like real HII regions: mostly red hydrogen, some blue reflection and teal oxygen
+    let mut rng = fastrand::Rng::with_seed(desc.seed as u64);
+    let nebulae: Vec<Nebula> = (0..desc.nebulae).map(|_| {
+        let (l, b) = (rng.f32() * std::f32::consts::TAU, (rng.f32() * 2.0 - 1.0) * width * 0.8);
+        let dir = (center * l.cos() * b.cos() + side * l.sin() * b.cos() + pole * b.sin()).normalize();
+        let color = match rng.f32() { k if k < 0.55 => vec3(1.0, 0.22, 0.3), k if k < 0.8 => vec3(0.35, 0.5, 1.0), _ => vec3(0.3, 0.9, 0.75) };
+        Nebula { dir, radius: desc.nebula_size.to_radians() * (0.5 + rng.f32()), color }
+    }).collect();
+
+    Ok(Cubemap::bake(desc.resolution, |d| {
+        let b = d.dot(&pole).clamp(-1.0, 1.0).asin();
+        let band = (-(b / width).powi(2)).exp();
+        let bulge = desc.bulge * (-(d.dot(&center).clamp(-1.0, 1.0).acos() / 0.25).powi(2)).exp();
+        let n = (clouds.get_noise_3d(d.x, d.y, d.z) + 1.0) * 0.5;
+        let stars = band * (0.3 + 0.7 * n * n) + bulge * (0.5 + 0.5 * n);
+        // Dust is thickest on the midplane and patchy, which is what splits the band into the Great Rift
+        let lanes = smoothstep(0.45, 0.65, (dust.get_noise_3d(d.x, d.y, d.z) + 1.0) * 0.5) * (-(b / (width * 0.45)).powi(2)).exp();
+        let light = stars * (1.0 - desc.dust.clamp(0.0, 1.0) * lanes);
+        // Older, yellower stars in the bulge; bluer in the disk
+        let tint = vec3(0.75, 0.82, 1.0).lerp(&vec3(1.0, 0.85, 0.62), (bulge / (stars + 1e-4)).clamp(0.0, 1.0));
+        let mut c = tint * light * desc.brightness * BAND_RADIANCE;
+        for neb in &nebulae {
+            let cos = d.dot(&neb.dir);
+            if cos < (neb.radius * 3.0).min(std::f32::consts::PI).cos() { continue; }
+            let r = cos.clamp(-1.0, 1.0).acos() / neb.radius;
+            let w = (wisps.get_noise_3d(d.x, d.y, d.z) + 1.0) * 0.5;
+            c += neb.color * ((-(r * r) * 1.5).exp() * (0.3 + 0.7 * w * w) * desc.nebula_brightness * NEBULA_RADIANCE);
+        }
+        c
+    }))
+}
@@ -0,0 +1,11 @@
+use minifb::{Key, KeyRepeat, Window};
+
+// Snapshot of the keyboard for one frame, so the simulation can run without a window
+#[derive(Default, Clone, Debug)]
+pub struct Input { down: Vec<Key>, pressed: Vec<Key> }
+
+impl Input {
+    pub fn from_window(window: &Window) -> Self { Self { down: window.get_keys(), pressed: window.get_keys_pressed(KeyRepeat::No) } }
+    pub fn is_key_down(&self, key: Key) -> bool { self.down.contains(&key) }
+    pub fn is_key_pressed(&self, key: Key) -> bool { self.pressed.contains(&key) }
+}
@@ -0,0 +1,5 @@
+pub mod color; pub mod framebuffer; pub mod fragment; pub mod vertex; pub mod triangle; pub mod obj; pub mod camera; pub mod shaders; pub mod skybox; pub mod input;
+pub mod renderer; pub mod bloom; pub mod ring; pub mod ship; pub mod scene; pub mod system; pub mod orbit; pub mod ephemeris; pub mod clock; pub mod clipping; pub mod texture; pub mod material; pub mod mesh; pub mod tonemap; pub mod light; pub mod shadow; pub mod cubemap; pub mod galaxy;
+
+pub use renderer::{render, Uniforms};
+pub use scene::{Scene, SceneConfig, SphereKind};
@@ -0,0 +1,19 @@
+use nalgebra_glm::{Vec3, vec3};
+
+// The star's light as seen by the shaders. Light reaching a point at distance d is
+// color * intensity / (1 + falloff * d^2); the scene's distances are compressed, so the default falloff of 0
+// keeps the outer planets as bright as the inner ones. `radius` is the star's, for soft shadows.
+#[derive(Clone, Copy, Debug)]
+pub struct PointLight { pub position: Vec3, pub color: Vec3, pub intensity: f32, pub falloff: f32, pub radius: f32 }
+
+impl Default for PointLight {
+    fn default() -> Self { Self { position: vec3(0.0, 0.0, 0.0), color: vec3(1.0, 1.0, 1.0), intensity: 1.0, falloff: 0.0, radius: 0.0 } }
+}
+
+impl PointLight {
+    // Unit direction from `p` towards the light, and the light arriving at `p`
+    pub fn incident(&self, p: Vec3) -> (Vec3, Vec3) {
+        let d = self.position - p; let dist2 = d.magnitude_squared();
+        (d / dist2.sqrt().max(1e-6), self.color * (self.intensity / (1.0 + self.falloff * dist2)))
+    }
+}
@@ -0,0 +1,62 @@
+use crate::{fragment::Fragment, texture::{Filter, Mapping, Texture, Wrap}};
+use nalgebra_glm::{Vec2, Vec3, vec3};
+use std::path::Path;
+
+// Surface description from an MTL file: Ka/Kd/Ks/Ke colors, Ns shininess, map_Kd and map_Bump/norm textures
+pub struct Material {
+    pub name: String,
+    pub ambient: Vec3,
+    pub diffuse: Vec3,
+    pub specular: Vec3,
+    pub emission: Vec3,
+    pub shininess: f32,
+    pub diffuse_map: Option<Texture>,
+    pub normal_map: Option<Texture>,
+}
+
+impl Default for Material {
+    // Used for meshes without a material (or when the MTL file can't be found)
+    fn default() -> Self { Self { name: "default".to_string(), ambient: vec3(1.0, 1.0, 1.0), diffuse: vec3(0.8, 0.8, 0.8), specular: vec3(0.0, 0.0, 0.0), emission: vec3(0.0, 0.0, 0.0), shininess: 32.0, diffuse_map: None, normal_map: None } }
+}
+
+impl Material {
+    // Texture paths in the MTL are relative to the directory of the OBJ
+    pub fn from_mtl(m: &tobj::Material, dir: &Path) -> Result<Self, Box<dyn std::error::Error>> {
+        let d = Material::default();
+        let rgb = |c: Option<[f32; 3]>, fallback: Vec3| c.map_or(fallback, |c| vec3(c[0], c[1], c[2]));
+        // tobj keeps Ke among the unrecognized parameters
+        let ke = m.unknown_param.get("Ke").and_then(|s| { let v: Vec<f32> = s.split_whitespace().filter_map(|t| t.parse().ok()).collect(); (v.len() == 3).then(|| [v[0], v[1], v[2]]) });
+        // A texture that can't be loaded is only a warning: the material falls back to Kd (or the geometric normal)
+        let map = |name: &Option<String>| name.as_ref().and_then(|file| {
+            Texture::load(&dir.join(file).to_string_lossy(), Filter::Bilinear, Wrap::Repeat, Mapping::Uv)
+                .map_err(|e| eprintln!("warning: material {}: {e}; using Kd instead", m.name)).ok()
+        });
+        Ok(Self {
+            name: m.name.clone(), ambient: rgb(m.ambient, d.ambient), diffuse: rgb(m.diffuse, d.diffuse), specular: rgb(m.specular, d.specular), emission: rgb(ke, d.emission),
+            shininess: m.shininess.unwrap_or(d.shininess), diffuse_map: map(&m.diffuse_texture), normal_map: map(&m.normal_texture),
+        })
+    }
+
+    // Emission strength for the framebuffer: Ke's brightest channel
+    pub fn emission_strength(&self) -> f32 { self.emission.max() }
+
+    // Diffuse color at `uv`: map_Kd when present (tinted by Kd), otherwise Kd
+    pub fn albedo(&self, uv: Vec2) -> Vec3 {
+        match &self.diffuse_map {
+            Some(t) => { let c = t.sample(uv); vec3(c.r as f32, c.g as f32, c.b as f32).component_mul(&self.diffuse) / 255.0 }
+            None => self.diffuse,
+        }
+    }
+
+    // World-space normal, perturbed by the tangent-space normal map when there is one
+    pub fn shading_normal(&self, fragment: &Fragment) -> Vec3 {
+        let n = fragment.normal.normalize();
+        let Some(map) = &self.normal_map else { return n };
+        let t = fragment.tangent - n * n.dot(&fragment.tangent);
+        if t.magnitude() < 1e-6 { return n; }
+        let t = t.normalize(); let b = n.cross(&t);
+        let c = map.sample(fragment.tex_coords);
+        let s = vec3(c.r as f32, c.g as f32, c.b as f32) / 127.5 - vec3(1.0, 1.0, 1.0);
+        (t * s.x + b * s.y + n * s.z).normalize()
+    }
+}
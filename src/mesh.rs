@@ -0,0 +1,147 @@
+use crate::{texture::spherical_uv, vertex::Vertex};
+use nalgebra_glm::{Vec3, vec3};
+use std::collections::HashMap;
+
+// Indexed triangle list: every three entries of `indices` form a triangle over `vertices`.
+// `render` transforms each vertex once per draw no matter how many triangles share it.
+#[derive(Clone, Debug, Default)]
+pub struct Mesh {
+    pub vertices: Vec<Vertex>,
+    pub indices: Vec<u32>,
+}
+
+impl Mesh {
+    pub fn new(vertices: Vec<Vertex>, indices: Vec<u32>) -> Self { Self { vertices, indices } }
+    pub fn triangle_count(&self) -> usize { self.indices.len() / 3 }
+}
+
+// Vertex on a sphere centered at the origin: the normal is the direction, UVs are longitude/latitude
+// (same convention as `texture::spherical_uv`) and the tangent points east
+fn sphere_vertex(dir: Vec3, radius: f32) -> Vertex {
+    let east = vec3(-dir.z, 0.0, dir.x);
+    let tangent = if east.magnitude() > 1e-6 { east.normalize() } else { Vec3::zeros() };
+    Vertex { tangent, ..Vertex::with_tex_coords(dir * radius, dir, spherical_uv(dir)) }
+}
+
+impl Mesh {
+    // Latitude/longitude sphere with `stacks` rings and `slices` segments. The seam column is duplicated
+    // so the UVs run cleanly from 0 to 1, which makes it the one to use with `uv`-mapped textures.
+    pub fn uv_sphere(stacks: u32, slices: u32, radius: f32) -> Self {
+        let (stacks, slices) = (stacks.max(2), slices.max(3));
+        let mut vertices = Vec::with_capacity(((stacks + 1) * (slices + 1)) as usize);
+        for i in 0..=stacks {
+            let v = i as f32 / stacks as f32;
+            let (st, ct) = (v * std::f32::consts::PI).sin_cos();
+            for j in 0..=slices {
+                let u = j as f32 / slices as f32;
+                let (sp, cp) = ((u - 0.5) * std::f32::consts::TAU).sin_cos();
+                let mut vert = sphere_vertex(vec3(st * cp, ct, st * sp), radius);
+                vert.tex_coords = nalgebra_glm::vec2(u, v);
+                vertices.push(vert);
+            }
+        }
+        let row = slices + 1;
+        let mut indices = Vec::with_capacity((stacks * slices * 6) as usize);
+        for i in 0..stacks { for j in 0..slices {
+            let (a, b, c, d) = (i * row + j, i * row + j + 1, (i + 1) * row + j, (i + 1) * row + j + 1);
+            // The pole rows collapse to a point, so each only needs one of the two triangles
+            if i != 0 { indices.extend([a, b, c]); }
+            if i != stacks - 1 { indices.extend([b, d, c]); }
+        }}
+        Self { vertices, indices }
+    }
+
+    // Icosahedron with every triangle split in four `subdivisions` times and pushed onto the sphere:
+    // evenly sized triangles, so it is the cheapest way to get a round silhouette
+    pub fn icosphere(subdivisions: u32, radius: f32) -> Self {
+        let t = (1.0 + 5f32.sqrt()) / 2.0;
+        let mut dirs: Vec<Vec3> = [
+            (-1.0, t, 0.0), (1.0, t, 0.0), (-1.0, -t, 0.0), (1.0, -t, 0.0),
+            (0.0, -1.0, t), (0.0, 1.0, t), (0.0, -1.0, -t), (0.0, 1.0, -t),
+            (t, 0.0, -1.0), (t, 0.0, 1.0), (-t, 0.0, -1.0), (-t, 0.0, 1.0),
+        ].iter().map(|&(x, y, z)| vec3(x, y, z).normalize()).collect();
+        let mut faces: Vec<[u32; 3]> = vec![
+            [0, 11, 5], [0, 5, 1], [0, 1, 7], [0, 7, 10], [0, 10, 11], [1, 5, 9], [5, 11, 4], [11, 10, 2], [10, 7, 6], [7, 1, 8],
+            [3, 9, 4], [3, 4, 2], [3, 2, 6], [3, 6, 8], [3, 8, 9], [4, 9, 5], [2, 4, 11], [6, 2, 10], [8, 6, 7], [9, 8, 1],
+        ];
+        for _ in 0..subdivisions {
+            // Edge midpoints are shared between the two triangles on either side
+            let mut midpoints: HashMap<(u32, u32), u32> = HashMap::new();
+            let mut mid = |a: u32, b: u32, dirs: &mut Vec<Vec3>| *midpoints.entry((a.min(b), a.max(b))).or_insert_with(|| { dirs.push((dirs[a as usize] + dirs[b as usize]).normalize()); dirs.len() as u32 - 1 });
+            faces = faces.iter().flat_map(|&[a, b, c]| {
+                let (ab, bc, ca) = (mid(a, b, &mut dirs), mid(b, c, &mut dirs), mid(c, a, &mut dirs));
+                [[a, ab, ca], [b, bc, ab], [c, ca, bc], [ab, bc, ca]]
+            }).collect();
+        }
+        Self { vertices: dirs.iter().map(|&d| sphere_vertex(d, radius)).collect(), indices: faces.concat() }
+    }
+}
+
+// Meshes of increasing detail for one shape; `select` picks by on-screen radius in pixels
+pub struct Lods { levels: Vec<(f32, Mesh)> }
+
+impl Lods {
+    // `levels` is (max radius in px, mesh), in increasing order; the last one is used above every threshold
+    pub fn new(levels: Vec<(f32, Mesh)>) -> Self { Self { levels } }
+    pub fn single(mesh: Mesh) -> Self { Self { levels: vec![(f32::INFINITY, mesh)] } }
+
+    // Icosphere from 80 triangles (a few pixels across) to 20480 (filling the screen)
+    pub fn icospheres(radius: f32) -> Self {
+        Self::new(vec![(8.0, Mesh::icosphere(1, radius)), (30.0, Mesh::icosphere(2, radius)), (120.0, Mesh::icosphere(3, radius)), (400.0, Mesh::icosphere(4, radius)), (f32::INFINITY, Mesh::icosphere(5, radius))])
+    }
+    pub fn uv_spheres(radius: f32) -> Self {
+        Self::new(vec![(8.0, Mesh::uv_sphere(6, 12, radius)), (30.0, Mesh::uv_sphere(12, 24, radius)), (120.0, Mesh::uv_sphere(24, 48, radius)), (400.0, Mesh::uv_sphere(48, 96, radius)), (f32::INFINITY, Mesh::uv_sphere(96, 192, radius))])
+    }
+
+    pub fn select(&self, radius_px: f32) -> &Mesh {
+        let (_, last) = self.levels.last().expect("Lods needs at least one level");
+        self.levels.iter().find(|(max_px, _)| radius_px < *max_px).map_or(last, |(_, m)| m)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_on_sphere(mesh: &Mesh, radius: f32) {
+        for v in &mesh.vertices {
+            assert!((v.position.magnitude() - radius).abs() < 1e-5, "{:?}", v.position);
+            assert!((v.normal - v.position / radius).magnitude() < 1e-5);
+        }
+        assert!(mesh.indices.iter().all(|&i| (i as usize) < mesh.vertices.len()));
+        // Every triangle winds the same way seen from outside, so back-face culling keeps the near half
+        let outward = |t: &[u32]| { let [a, b, c] = [0, 1, 2].map(|k| mesh.vertices[t[k] as usize].position); (b - a).cross(&(c - a)).dot(&(a + b + c)) };
+        let sign = outward(&mesh.indices[0..3]).signum();
+        assert!(mesh.indices.chunks_exact(3).all(|t| outward(t) * sign > 0.0));
+    }
+
+    #[test]
+    fn icosphere_counts_and_radius() {
+        for n in 0..4 {
+            let mesh = Mesh::icosphere(n, 0.5);
+            assert_eq!(mesh.triangle_count(), 20 * 4usize.pow(n));
+            // Euler: V = E - F + 2 with E = 3F / 2, so no midpoint was duplicated
+            assert_eq!(mesh.vertices.len(), 10 * 4usize.pow(n) + 2);
+            assert_on_sphere(&mesh, 0.5);
+        }
+    }
+
+    #[test]
+    fn uv_sphere_counts_and_radius() {
+        for (stacks, slices) in [(2, 3), (6, 12), (24, 48)] {
+            let mesh = Mesh::uv_sphere(stacks, slices, 2.0);
+            // One triangle per segment in the two pole rows, two everywhere else
+            assert_eq!(mesh.triangle_count(), (2 * slices * (stacks - 1)) as usize);
+            assert_eq!(mesh.vertices.len(), ((stacks + 1) * (slices + 1)) as usize);
+            assert_on_sphere(&mesh, 2.0);
+        }
+    }
+
+    #[test]
+    fn lods_pick_by_screen_radius() {
+        let lods = Lods::icospheres(0.5);
+        assert_eq!(lods.select(2.0).triangle_count(), 80);
+        assert_eq!(lods.select(8.0).triangle_count(), 320);
+        assert_eq!(lods.select(5000.0).triangle_count(), 20480);
+    }
+}
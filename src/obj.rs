@@ -1,15 +1,60 @@
 use nalgebra_glm::{Vec2, Vec3};
-use tobj;
-use crate::vertex::Vertex;
+use crate::{material::Material, mesh::Mesh, vertex::Vertex};
+use std::path::Path;
 
-pub struct Obj { meshes: Vec<Mesh> }
-struct Mesh { vertices: Vec<Vec3>, normals: Vec<Vec3>, texcoords: Vec<Vec2>, indices: Vec<u32> }
+pub struct Obj { meshes: Vec<ObjMesh>, materials: Vec<Material> }
+struct ObjMesh { vertices: Vec<Vec3>, normals: Vec<Vec3>, texcoords: Vec<Vec2>, tangents: Vec<Vec3>, indices: Vec<u32>, material: usize }
+
+// Triangles of one OBJ mesh and the index of their material in `Model::materials`
+pub struct Part { pub mesh: Mesh, pub material: usize }
+pub struct Model { pub parts: Vec<Part>, pub materials: Vec<Material> }
+
+impl Model {
+    pub fn vertices(&self) -> impl Iterator<Item = &Vertex> { self.parts.iter().flat_map(|p| p.mesh.vertices.iter()) }
+}
 
 impl Obj {
     pub fn load(filename: &str) -> Result<Self, Box<dyn std::error::Error>> {
-        let (models, _mats) = tobj::load_obj(filename, &tobj::LoadOptions { single_index: true, triangulate: true, ..Default::default() })?;
-        let meshes = models.into_iter().map(|m| { let mesh = m.mesh; Mesh { vertices: mesh.positions.chunks(3).map(|v| Vec3::new(v[0], v[1], v[2])).collect(), normals: mesh.normals.chunks(3).map(|n| Vec3::new(n[0], n[1], n[2])).collect(), texcoords: mesh.texcoords.chunks(2).map(|t| Vec2::new(t[0], 1.0 - t[1])).collect(), indices: mesh.indices } }).collect();
-        Ok(Obj { meshes })
+        let (models, mats) = tobj::load_obj(filename, &tobj::LoadOptions { single_index: true, triangulate: true, ..Default::default() })?;
+        // A missing MTL file is not an error: those meshes just get the default material (last entry)
+        let dir = Path::new(filename).parent().unwrap_or(Path::new("."));
+        let mut materials = mats.unwrap_or_default().iter().map(|m| Material::from_mtl(m, dir)).collect::<Result<Vec<_>, _>>()?;
+        let default_material = materials.len(); materials.push(Material::default());
+        let meshes = models.into_iter().map(|m| { let mesh = m.mesh; let material = mesh.material_id.filter(|&i| i < default_material).unwrap_or(default_material); let mut mesh = ObjMesh { vertices: mesh.positions.chunks(3).map(|v| Vec3::new(v[0], v[1], v[2])).collect(), normals: mesh.normals.chunks(3).map(|n| Vec3::new(n[0], n[1], n[2])).collect(), texcoords: mesh.texcoords.chunks(2).map(|t| Vec2::new(t[0], 1.0 - t[1])).collect(), tangents: Vec::new(), indices: mesh.indices, material }; mesh.tangents = compute_tangents(&mesh); mesh }).collect();
+        Ok(Obj { meshes, materials })
+    }
+    // All meshes merged into one indexed mesh (materials ignored)
+    pub fn get_mesh(&self) -> Mesh {
+        let mut out = Mesh::default();
+        for m in &self.meshes { let base = out.vertices.len() as u32; out.vertices.extend(mesh_vertices(m)); out.indices.extend(m.indices.iter().map(|i| i + base)); }
+        out
+    }
+    // One part per mesh, keeping the materials from the MTL file
+    pub fn into_model(self) -> Model { Model { parts: self.meshes.iter().map(|m| Part { mesh: Mesh::new(mesh_vertices(m), m.indices.clone()), material: m.material }).collect(), materials: self.materials } }
+}
+
+// One `Vertex` per unique OBJ vertex (tobj's single_index already merged position/normal/UV triples)
+fn mesh_vertices(mesh: &ObjMesh) -> Vec<Vertex> {
+    (0..mesh.vertices.len()).map(|i| {
+        let pos = mesh.vertices[i];
+        let normal = mesh.normals.get(i).copied().unwrap_or_else(|| pos.normalize());
+        let uv = mesh.texcoords.get(i).copied().unwrap_or_else(Vec2::zeros);
+        Vertex { tangent: mesh.tangents.get(i).copied().unwrap_or_else(Vec3::zeros), ..Vertex::with_tex_coords(pos, normal, uv) }
+    }).collect()
+}
+
+// Per-vertex tangents (dP/du) accumulated over the triangles that share the vertex
+fn compute_tangents(mesh: &ObjMesh) -> Vec<Vec3> {
+    if mesh.texcoords.len() != mesh.vertices.len() { return Vec::new(); }
+    let mut tangents = vec![Vec3::zeros(); mesh.vertices.len()];
+    for tri in mesh.indices.chunks_exact(3) {
+        let [a, b, c] = [tri[0] as usize, tri[1] as usize, tri[2] as usize];
+        let (e1, e2) = (mesh.vertices[b] - mesh.vertices[a], mesh.vertices[c] - mesh.vertices[a]);
+        let (d1, d2) = (mesh.texcoords[b] - mesh.texcoords[a], mesh.texcoords[c] - mesh.texcoords[a]);
+        let det = d1.x * d2.y - d2.x * d1.y;
+        if det.abs() < 1e-12 { continue; }
+        let t = (e1 * d2.y - e2 * d1.y) / det;
+        for i in [a, b, c] { tangents[i] += t; }
     }
-    pub fn get_vertex_array(&self) -> Vec<Vertex> { let mut v = Vec::new(); for mesh in &self.meshes { for &idx in &mesh.indices { let i = idx as usize; let pos = mesh.vertices[i]; let normal = mesh.normals.get(i).copied().unwrap_or_else(|| pos.normalize()); v.push(Vertex::new(pos, normal)); } } v }
+    tangents.iter().map(|t| if t.magnitude() > 1e-12 { t.normalize() } else { *t }).collect()
 }
@@ -0,0 +1,68 @@
+use nalgebra_glm::{Vec3, vec3};
+
+// Keplerian orbit from classical elements. Angles are in radians, `semi_major_axis` in world units
+// and `mean_motion` in radians per simulated second. The reference plane is world XZ with +Y as north.
+#[derive(Clone, Copy, Debug)]
+pub struct Orbit {
+    pub semi_major_axis: f32,
+    pub eccentricity: f32,
+    pub inclination: f32,
+    pub ascending_node: f32,
+    pub arg_periapsis: f32,
+    pub mean_anomaly: f32,
+    pub mean_motion: f32,
+}
+
+// Solves Kepler's equation M = E - e sin E for the eccentric anomaly E (Newton-Raphson)
+pub fn solve_kepler(mean_anomaly: f32, eccentricity: f32) -> f32 {
+    let m = mean_anomaly.rem_euclid(std::f32::consts::TAU);
+    let e = eccentricity.clamp(0.0, 0.99);
+    let mut ea = if e < 0.8 { m } else { std::f32::consts::PI };
+    for _ in 0..12 {
+        let f = ea - e * ea.sin() - m;
+        let d = f / (1.0 - e * ea.cos());
+        ea -= d;
+        if d.abs() < 1e-6 { break; }
+    }
+    ea
+}
+
+impl Orbit {
+    // Accumulated in f64 so long warped runs keep their precision
+    pub fn mean_anomaly_at(&self, tsec: f64) -> f32 { (self.mean_anomaly as f64 + self.mean_motion as f64 * tsec).rem_euclid(std::f64::consts::TAU) as f32 }
+
+    // Position relative to the focus for a given mean anomaly
+    pub fn position_at_mean_anomaly(&self, mean_anomaly: f32) -> Vec3 {
+        let e = self.eccentricity;
+        let ea = solve_kepler(mean_anomaly, e);
+        // Perifocal coordinates
+        let px = self.semi_major_axis * (ea.cos() - e);
+        let py = self.semi_major_axis * (1.0 - e * e).max(0.0).sqrt() * ea.sin();
+        // Rotate by argument of periapsis, inclination and longitude of ascending node
+        let (sw, cw) = self.arg_periapsis.sin_cos(); let (si, ci) = self.inclination.sin_cos(); let (so, co) = self.ascending_node.sin_cos();
+        let x = (co * cw - so * sw * ci) * px + (-co * sw - so * cw * ci) * py;
+        let y = (so * cw + co * sw * ci) * px + (-so * sw + co * cw * ci) * py;
+        let z = (sw * si) * px + (cw * si) * py;
+        // Ecliptic (x, y, z-north) to right-handed world with +Y up: (x, z, -y)
+        vec3(x, z, -y)
+    }
+
+    pub fn position(&self, tsec: f64) -> Vec3 { self.position_at_mean_anomaly(self.mean_anomaly_at(tsec)) }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn kepler_converges_up_to_high_eccentricity() {
+        for e in [0.0, 0.3, 0.7, 0.9, 0.97, 0.99] {
+            for i in 0..=64 {
+                let m = i as f32 / 64.0 * std::f32::consts::TAU;
+                let ea = solve_kepler(m, e);
+                let residual = (ea - e * ea.sin() - m.rem_euclid(std::f32::consts::TAU) + std::f32::consts::PI).rem_euclid(std::f32::consts::TAU) - std::f32::consts::PI;
+                assert!(residual.abs() < 1e-4, "e = {e}, M = {m}: residual {residual}");
+            }
+        }
+    }
+}
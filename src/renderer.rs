@@ -0,0 +1,235 @@
+use crate::{clipping::{clip_segment, clip_triangle, to_screen}, color::{Color, Rgba}, fragment::Fragment, framebuffer::{Framebuffer, Tile}, light::PointLight, shadow::Occluder, mesh::Mesh, shaders, texture::Texture, triangle::{triangle_bounds, triangle_stream, Rect}, vertex::Vertex};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use fastnoise_lite::FastNoiseLite;
+use nalgebra_glm::{Mat4, Vec3, Vec4, vec3};
+
+pub struct Uniforms<'a> { pub model_matrix: Mat4, pub view_matrix: Mat4, pub projection_matrix: Mat4, pub viewport_matrix: Mat4, pub time: f32, pub noises: Vec<&'a FastNoiseLite>, pub camera_pos: Vec3, pub texture: Option<&'a Texture>, pub light: PointLight, pub occluders: &'a [Occluder] }
+
+pub fn create_viewport_matrix(width: f32, height: f32) -> Mat4 { Mat4::new(width/2.0,0.0,0.0,width/2.0, 0.0,-height/2.0,0.0,height/2.0, 0.0,0.0,1.0,0.0, 0.0,0.0,0.0,1.0) }
+pub fn create_model_matrix(translation: Vec3, scale: f32, rotation_y: f32) -> Mat4 { let (s,c) = rotation_y.sin_cos(); let rot_y = Mat4::new(c,0.0,s,0.0, 0.0,1.0,0.0,0.0, -s,0.0,c,0.0, 0.0,0.0,0.0,1.0); let transform = Mat4::new(scale,0.0,0.0,translation.x, 0.0,scale,0.0,translation.y, 0.0,0.0,scale,translation.z, 0.0,0.0,0.0,1.0); transform*rot_y }
+pub fn create_model_matrix_euler(translation: Vec3, scale: f32, rx: f32, ry: f32, rz: f32) -> Mat4 { let (sx,cx) = rx.sin_cos(); let (sy,cy) = ry.sin_cos(); let (sz,cz) = rz.sin_cos(); let rxm = Mat4::new(1.0,0.0,0.0,0.0, 0.0,cx,-sx,0.0, 0.0,sx,cx,0.0, 0.0,0.0,0.0,1.0); let rym = Mat4::new(cy,0.0,sy,0.0, 0.0,1.0,0.0,0.0, -sy,0.0,cy,0.0, 0.0,0.0,0.0,1.0); let rzm = Mat4::new(cz,-sz,0.0,0.0, sz,cz,0.0,0.0, 0.0,0.0,1.0,0.0, 0.0,0.0,0.0,1.0); let s = Mat4::new(scale,0.0,0.0,translation.x, 0.0,scale,0.0,translation.y, 0.0,0.0,scale,translation.z, 0.0,0.0,0.0,1.0); s*rzm*rym*rxm }
+
+// Tiles are TILE_SIZE x TILE_SIZE pixels; each one is shaded by a single thread
+pub const TILE_SIZE: i32 = 64;
+// Draws touching fewer tiles than this (distant bodies, small ship parts) are shaded inline: spawning the workers
+// would cost more than the shading
+const MIN_PARALLEL_TILES: usize = 4;
+
+// Triangles are binned into the screen tiles their bounding boxes touch and the tiles are shaded in parallel.
+// Each tile keeps the triangles in submission order, so the result matches a serial `Framebuffer::point` pass.
+pub fn render<C: Into<Rgba>, F: Fn(&Fragment) -> C + Sync>(fb: &mut Framebuffer, uniforms: &Uniforms, mesh: &Mesh, shader_fn: F) {
+    render_with_threads(fb, uniforms, mesh, shader_fn, std::thread::available_parallelism().map_or(1, |n| n.get()));
+}
+
+// `render` with at most `max_threads` workers
+fn render_with_threads<C: Into<Rgba>, F: Fn(&Fragment) -> C + Sync>(fb: &mut Framebuffer, uniforms: &Uniforms, mesh: &Mesh, shader_fn: F, max_threads: usize) {
+    // Vertex stage: each unique vertex once
+    let vt = shaders::VertexTransforms::new(uniforms);
+    let shaded: Vec<Vertex> = mesh.vertices.iter().map(|v| shaders::vertex_shader(v, &vt)).collect();
+
+    // Primitive assembly: clip against the frustum before the perspective divide
+    let mut transformed = Vec::with_capacity(mesh.indices.len());
+    for tri in mesh.indices.chunks_exact(3) { clip_triangle(&shaded[tri[0] as usize], &shaded[tri[1] as usize], &shaded[tri[2] as usize], &uniforms.viewport_matrix, &mut transformed); }
+
+    // Binning
+    let screen = Rect { x0: 0, y0: 0, x1: fb.width as i32 - 1, y1: fb.height as i32 - 1 };
+    let tiles_x = (fb.width as i32 + TILE_SIZE - 1) / TILE_SIZE; let tiles_y = (fb.height as i32 + TILE_SIZE - 1) / TILE_SIZE;
+    let mut bins: Vec<Vec<usize>> = vec![Vec::new(); (tiles_x * tiles_y) as usize];
+    for (t, tri) in transformed.chunks_exact(3).enumerate() {
+        let Some(r) = triangle_bounds(&tri[0], &tri[1], &tri[2], &screen) else { continue };
+        for ty in r.y0 / TILE_SIZE..=r.y1 / TILE_SIZE { for tx in r.x0 / TILE_SIZE..=r.x1 / TILE_SIZE { bins[(ty * tiles_x + tx) as usize].push(t); } }
+    }
+    let work: Vec<(Rect, &[usize])> = bins.iter().enumerate().filter(|(_, b)| !b.is_empty()).map(|(i, b)| {
+        let (tx, ty) = (i as i32 % tiles_x, i as i32 / tiles_x);
+        let rect = Rect { x0: tx * TILE_SIZE, y0: ty * TILE_SIZE, x1: ((tx + 1) * TILE_SIZE - 1).min(screen.x1), y1: ((ty + 1) * TILE_SIZE - 1).min(screen.y1) };
+        (rect, b.as_slice())
+    }).collect();
+    if work.is_empty() { return; }
+
+    // Fragment stage
+    let fb_ref: &Framebuffer = fb;
+    let samples = fb.sample_offsets();
+    let shade_tile = |(rect, tris): &(Rect, &[usize])| {
+        let mut tile = fb_ref.read_tile(*rect);
+        for &t in tris.iter() {
+            let (a, b, c) = (&transformed[t*3], &transformed[t*3+1], &transformed[t*3+2]);
+            triangle_stream(a, b, c, rect, samples, |frag, cov| {
+                let x = frag.position.x as i32; let y = frag.position.y as i32;
+                let mask = tile.visible(x, y, cov);
+                if mask != 0 { tile.point(x, y, mask, cov, shader_fn(frag).into()); }
+            });
+        }
+        tile
+    };
+    let threads = if work.len() < MIN_PARALLEL_TILES { 1 } else { max_threads.min(work.len()) };
+    let tiles: Vec<Tile> = if threads <= 1 { work.iter().map(shade_tile).collect() } else {
+        // Workers pull tiles from a shared counter so a few expensive tiles don't stall one thread
+        let next = AtomicUsize::new(0);
+        std::thread::scope(|s| {
+            let handles: Vec<_> = (0..threads).map(|_| s.spawn(|| {
+                let mut done = Vec::new();
+                while let Some(job) = work.get(next.fetch_add(1, Ordering::Relaxed)) { done.push(shade_tile(job)); }
+                done
+            })).collect();
+            handles.into_iter().flat_map(|h| h.join().expect("render worker panicked")).collect()
+        })
+    };
+    for tile in &tiles { fb.write_tile(tile); }
+}
+
+pub fn project_point(view: &Mat4, proj: &Mat4, vp: &Mat4, p: Vec3) -> Option<(i32,i32)> {
+    let hp = Vec4::new(p.x,p.y,p.z,1.0);
+    let clip = *proj * *view * hp;
+
+    if clip.w <= 1e-6 { return None; }
+    let ndc = Vec4::new(clip.x/clip.w, clip.y/clip.w, clip.z/clip.w, 1.0);
+
+    if ndc.z < -1.0 || ndc.z > 1.0 { return None; }
+    let screen = *vp * ndc; Some((screen.x as i32, screen.y as i32))
+}
+
+// On-screen length of `scale` world units at `center`, measured along the camera's right axis so it
+// doesn't depend on which way the body is turned
+pub fn screen_radius_px(view: &Mat4, proj: &Mat4, vp: &Mat4, center: Vec3, scale: f32) -> Option<f32> {
+    let right = vec3(view[(0, 0)], view[(0, 1)], view[(0, 2)]);
+    let p0 = project_point(view, proj, vp, center)?;
+    let p1 = project_point(view, proj, vp, center + right * scale)?;
+    let dx = (p1.0 - p0.0) as f32; let dy = (p1.1 - p0.1) as f32;
+    Some((dx*dx + dy*dy).sqrt())
+}
+
+// World-space polyline through `points`, `width` pixels wide, with segment i in `color(i)`. Clipped to the frustum,
+// then drawn anti-aliased and depth-tested by `Framebuffer::draw_line_aa`, so it hides behind whatever is in front
+#[allow(clippy::too_many_arguments)]
+pub fn draw_polyline_world(fb: &mut Framebuffer, view: &Mat4, proj: &Mat4, vp: &Mat4, points: &[Vec3], width: f32, color: impl Fn(usize) -> Color) {
+    let vpm = *proj * *view;
+    let clip: Vec<Vec4> = points.iter().map(|p| vpm * Vec4::new(p.x, p.y, p.z, 1.0)).collect();
+    draw_polyline_clip(fb, vp, &clip, width, color);
+}
+
+// Polyline already in clip space, clipped against the frustum segment by segment
+pub fn draw_polyline_clip(fb: &mut Framebuffer, vp: &Mat4, clip: &[Vec4], width: f32, color: impl Fn(usize) -> Color) {
+    for (i, seg) in clip.windows(2).enumerate() {
+        let Some((t0, t1)) = clip_segment(&seg[0], &seg[1]) else { continue };
+        let (a, b) = (to_screen(&seg[0].lerp(&seg[1], t0), vp), to_screen(&seg[0].lerp(&seg[1], t1), vp));
+        fb.set_current_color(color(i).to_hex());
+        // Ends cut by the frustum or at the ends of the polyline get a cap; joints are shared with the neighbour
+        fb.draw_line_aa(a, b, width, i == 0 || t0 > 0.0, i + 2 == clip.len() || t1 < 1.0);
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn draw_line_world(fb: &mut Framebuffer, view: &Mat4, proj: &Mat4, vp: &Mat4, a: Vec3, b: Vec3, width: f32, color: Color) {
+    draw_polyline_world(fb, view, proj, vp, &[a, b], width, |_| color);
+}
+
+// Circle on the horizontal plane through `center`
+#[allow(clippy::too_many_arguments)]
+pub fn draw_circle_world(fb: &mut Framebuffer, view: &Mat4, proj: &Mat4, vp: &Mat4, center: Vec3, radius: f32, segments: i32, width: f32, color: Color) {
+    let points: Vec<Vec3> = (0..=segments).map(|i| { let t = i as f32 / segments as f32 * std::f32::consts::TAU; center + vec3(t.cos() * radius, 0.0, t.sin() * radius) }).collect();
+    draw_polyline_world(fb, view, proj, vp, &points, width, |_| color);
+}
+
+pub fn scale_color(c: Color, k: f32) -> Color {
+    let k = k.clamp(0.0, 1.0);
+    Color::new(
+        ((c.r as f32)*k) as u8,
+        ((c.g as f32)*k) as u8,
+        ((c.b as f32)*k) as u8,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::light::PointLight;
+
+    const W: usize = 200;
+    const H: usize = 150;
+
+    fn uniforms() -> Uniforms<'static> {
+        Uniforms { model_matrix: Mat4::identity(), view_matrix: Mat4::identity(), projection_matrix: Mat4::identity(), viewport_matrix: create_viewport_matrix(W as f32, H as f32), time: 0.0, noises: vec![], camera_pos: Vec3::zeros(), texture: None, light: PointLight::default(), occluders: &[] }
+    }
+
+    // Depth-tested sphere in front of an overlapping one, spanning most of the screen's tiles
+    fn scene_mesh() -> Mesh {
+        let (a, b) = (Mesh::icosphere(2, 0.7), Mesh::icosphere(2, 0.5));
+        let n = a.vertices.len() as u32;
+        let moved = b.vertices.into_iter().map(|mut v| { v.position += vec3(0.4, 0.2, -0.3); v });
+        Mesh::new(a.vertices.into_iter().chain(moved).collect(), a.indices.into_iter().chain(b.indices.into_iter().map(|i| i + n)).collect())
+    }
+
+    fn shade(frag: &Fragment) -> Color { Color::from_float(frag.normal.x * 0.5 + 0.5, frag.normal.y * 0.5 + 0.5, frag.depth * 0.5 + 0.5) }
+
+    // Serial reference: every triangle streamed over the whole screen as one tile, in submission order
+    fn render_serial(fb: &mut Framebuffer, u: &Uniforms, mesh: &Mesh) {
+        let vt = shaders::VertexTransforms::new(u);
+        let mut tris = Vec::new();
+        for t in mesh.indices.chunks_exact(3) {
+            let [a, b, c] = [0, 1, 2].map(|k| shaders::vertex_shader(&mesh.vertices[t[k] as usize], &vt));
+            clip_triangle(&a, &b, &c, &u.viewport_matrix, &mut tris);
+        }
+        let screen = Rect { x0: 0, y0: 0, x1: fb.width as i32 - 1, y1: fb.height as i32 - 1 };
+        let (samples, mut tile) = (fb.sample_offsets(), fb.read_tile(screen));
+        for t in tris.chunks_exact(3) {
+            triangle_stream(&t[0], &t[1], &t[2], &screen, samples, |frag, cov| {
+                let (x, y) = (frag.position.x as i32, frag.position.y as i32);
+                let mask = tile.visible(x, y, cov);
+                if mask != 0 { tile.point(x, y, mask, cov, shade(frag).into()); }
+            });
+        }
+        fb.write_tile(&tile);
+    }
+
+    #[test]
+    fn tiled_render_matches_the_serial_pass() {
+        let (u, mesh) = (uniforms(), scene_mesh());
+        for samples in [1, 4] {
+            let mut reference = Framebuffer::with_samples(W, H, samples);
+            render_serial(&mut reference, &u, &mesh);
+            for threads in [1, 3] {
+                let mut fb = Framebuffer::with_samples(W, H, samples);
+                render_with_threads(&mut fb, &u, &mesh, shade, threads);
+                assert!(fb.buffer == reference.buffer && fb.zbuffer == reference.zbuffer && fb.hdr == reference.hdr, "{samples} samples, {threads} threads");
+            }
+            assert!(reference.zbuffer.iter().any(|z| z.is_finite()));
+        }
+    }
+
+    #[test]
+    fn shared_vertices_render_like_unshared_ones() {
+        // The same triangles with every corner duplicated: caching transforms per index must not change a pixel
+        let mut u = uniforms();
+        u.model_matrix = create_model_matrix_euler(vec3(0.1, -0.1, 0.0), 1.2, 0.3, 0.5, 0.1);
+        let mesh = scene_mesh();
+        let flat = Mesh::new(mesh.indices.iter().map(|&i| mesh.vertices[i as usize].clone()).collect(), (0..mesh.indices.len() as u32).collect());
+        assert!(mesh.vertices.len() * 4 < flat.vertices.len());
+        let (mut a, mut b) = (Framebuffer::new(W, H), Framebuffer::new(W, H));
+        render(&mut a, &u, &mesh, shade); render(&mut b, &u, &flat, shade);
+        assert!(a.buffer == b.buffer && a.zbuffer == b.zbuffer);
+    }
+
+    #[test]
+    fn blended_quad_has_no_seam() {
+        // Two triangles split along the diagonal through the pixel centers: each pixel is blended exactly once
+        let quad = Mesh::new([(-1.0, -1.0), (1.0, -1.0), (1.0, 1.0), (-1.0, 1.0)].iter().map(|&(x, y)| Vertex::new(vec3(x, y, 0.0), vec3(0.0, 0.0, 1.0))).collect(), vec![0, 1, 2, 0, 2, 3]);
+        let mut u = uniforms();
+        u.viewport_matrix = create_viewport_matrix(64.0, 64.0);
+        let mut fb = Framebuffer::new(64, 64);
+        fb.set_blend(crate::framebuffer::Blend::Alpha);
+        render(&mut fb, &u, &quad, |_| Color::new(255, 255, 255).with_alpha(0.5));
+        assert!(fb.buffer.iter().all(|&c| c == 0xbcbcbc));
+    }
+
+    #[test]
+    fn vertex_transforms_match_the_uniforms() {
+        let mut u = uniforms();
+        u.model_matrix = create_model_matrix(vec3(0.2, 0.0, -0.1), 0.5, 0.7);
+        u.projection_matrix = nalgebra_glm::perspective(W as f32 / H as f32, 1.0, 0.1, 10.0);
+        u.view_matrix = nalgebra_glm::look_at(&vec3(0.0, 0.5, 2.0), &Vec3::zeros(), &vec3(0.0, 1.0, 0.0));
+        let v = shaders::vertex_shader(&Vertex::new(vec3(0.3, -0.2, 0.4), vec3(0.0, 0.0, 1.0)), &shaders::VertexTransforms::new(&u));
+        let clip = u.projection_matrix * u.view_matrix * u.model_matrix * Vec4::new(0.3, -0.2, 0.4, 1.0);
+        assert!((v.clip_position - clip).magnitude() < 1e-5);
+        assert!((v.transformed_position - to_screen(&clip, &u.viewport_matrix)).magnitude() < 1e-3);
+    }
+}
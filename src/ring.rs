@@ -0,0 +1,36 @@
+use crate::{framebuffer::Framebuffer, light::PointLight, mesh::Mesh, shadow::Occluder, renderer::{create_model_matrix, render, Uniforms}, shaders, system::RingDesc, vertex::Vertex};
+use nalgebra_glm::{Mat4, Vec3, vec2, vec3};
+
+// Indexed annulus: an outer and an inner vertex per step around the ring (the last pair repeats the first
+// with v = 1 so the UVs don't wrap backwards)
+pub fn generate_ring_mesh(segments: usize, inner_r: f32, outer_r: f32, ellipse_z: f32) -> Mesh {
+    let mut verts = Vec::with_capacity((segments + 1) * 2);
+    let n = vec3(0.0, 1.0, 0.0);
+    for i in 0..=segments {
+        // u runs from the inner edge (0) to the outer edge (1), v around the ring
+        let v = i as f32 / segments as f32;
+        let (s, c) = (v * std::f32::consts::TAU).sin_cos();
+        verts.push(Vertex::with_tex_coords(vec3(c * outer_r, 0.0, s * outer_r * ellipse_z), n, vec2(1.0, v)));
+        verts.push(Vertex::with_tex_coords(vec3(c * inner_r, 0.0, s * inner_r * ellipse_z), n, vec2(0.0, v)));
+    }
+    let mut indices = Vec::with_capacity(segments * 6);
+    for i in 0..segments as u32 {
+        let (o0, i0, o1, i1) = (2 * i, 2 * i + 1, 2 * i + 2, 2 * i + 3);
+        indices.extend([o0, i0, i1, o0, i1, o1]);
+    }
+    Mesh::new(verts, indices)
+}
+
+// Rings are built in units of the body's `scale` (diameter) and scaled by the model matrix
+#[allow(clippy::too_many_arguments)]
+pub fn render_ring_with_segments(fb: &mut Framebuffer, view: &Mat4, proj: &Mat4, vp: &Mat4, light: &PointLight, occluders: &[Occluder], center: Vec3, planet_scale: f32, rotate_y: f32, ring: &RingDesc, segments: usize) {
+    let mesh = generate_ring_mesh(segments, ring.inner, ring.outer, ring.ellipse);
+    let model = create_model_matrix(center, planet_scale, rotate_y);
+    let u = Uniforms { model_matrix: model, view_matrix: *view, projection_matrix: *proj, viewport_matrix: *vp, time: 0.0, noises: vec![], camera_pos: vec3(0.0,0.0,0.0), texture: None, light: *light, occluders };
+    render(fb, &u, &mesh, |frag| shaders::fragment_ring(frag, &u, ring));
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn render_ring(fb: &mut Framebuffer, view: &Mat4, proj: &Mat4, vp: &Mat4, light: &PointLight, occluders: &[Occluder], center: Vec3, planet_scale: f32, rotate_y: f32, ring: &RingDesc) {
+    render_ring_with_segments(fb, view, proj, vp, light, occluders, center, planet_scale, rotate_y, ring, 128);
+}
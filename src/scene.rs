@@ -0,0 +1,402 @@
+use crate::{
+    clock::{FixedStep, SimClock, FIXED_DT},
+    camera::{bezier3, ease_in_out_cubic, ease_out_back, rotate_around_axis, FreeOrbitCamera},
+    bloom::Bloom, color::Color, framebuffer::{Blend, Framebuffer}, input::Input, obj::{Model, Obj},
+    renderer::{create_model_matrix, create_model_matrix_euler, create_viewport_matrix, draw_polyline_world, render, scale_color, screen_radius_px, Uniforms},
+    ring::render_ring_with_segments, shaders::{self, lambert},
+    ship::{clamp_ship_sphere, spawn_asteroid_crossing_ship, Asteroid, Lcg, Ship},
+    ephemeris::format_date, skybox::Skybox, system::{ShaderKind, SimTime, SolarSystem}, mesh::Lods, shadow::Occluder,
+};
+use fastnoise_lite::{FastNoiseLite, FractalType, NoiseType};
+use minifb::Key;
+use nalgebra_glm::{Mat4, Vec3, vec3};
+
+pub fn create_noise_fbmn(seed: i32, freq: f32, octaves: i32) -> FastNoiseLite { let mut n = FastNoiseLite::with_seed(seed); n.set_noise_type(Some(NoiseType::Perlin)); n.set_fractal_type(Some(FractalType::FBm)); n.set_fractal_octaves(Some(octaves)); n.set_frequency(Some(freq)); n }
+
+// Blended draws queued during the opaque pass with their distance to the camera (see `framebuffer::Blend`)
+#[derive(Clone, Copy)]
+enum Translucent {
+    Clouds { body: usize, pos: Vec3, radius_px: f32 },
+    Atmosphere { body: usize, pos: Vec3, radius_px: f32 },
+    Rings { body: usize, pos: Vec3, radius_px: f32 },
+    Explosion { pos: Vec3, scale: f32, t: f32 },
+}
+
+// Built-in sphere used for the bodies when no sphere OBJ is given
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SphereKind { Ico, Uv }
+
+// `start_date` is a Julian day; when set, bodies with mean elements are placed for that date.
+// `bloom` is the bloom intensity, 0 turns it off. `stars_path` is the star catalog for the sky and
+// `constellations` starts with its stick figures shown. `synthetic_stars` adds random faint stars (not a real catalog)
+pub struct SceneConfig { pub sphere_path: Option<String>, pub sphere_kind: SphereKind, pub ship_path: String, pub system_path: String, pub start_time: f64, pub start_date: Option<f64>, pub bloom: f32, pub stars_path: String, pub constellations: bool, pub synthetic_stars: bool }
+
+impl Default for SceneConfig {
+    fn default() -> Self { Self { sphere_path: None, sphere_kind: SphereKind::Ico, ship_path: "assets/models/SpaceShip.obj".to_string(), system_path: "assets/systems/solar.toml".to_string(), start_time: 0.0, start_date: None, bloom: 2.0, stars_path: "assets/sky/stars.toml".to_string(), constellations: false, synthetic_stars: false } }
+}
+
+pub struct Scene {
+    viewport: Mat4, aspect: f32, base_fov_deg: f32, near: f32, far: f32,
+    pub camera: FreeOrbitCamera, pub ship: Ship, prev_ship: Ship, sky: Skybox, bloom: Bloom,
+    spheres: Lods, ship_model: Model, asteroid_model: Model, asteroid_unit_scale: f32,
+    asteroid_noises: [FastNoiseLite; 2],
+    pub system: SolarSystem,
+    pub clock: SimClock, date_origin: Option<(f64, f64)>, physics: FixedStep,
+    rng: Lcg, asteroids: Vec<Asteroid>, max_asteroids: usize,
+    cam_detached: bool, cam_warp_target: Option<(Vec3, Vec3)>, cam_warp_origin_eye: Vec3, cam_warp_origin_center: Vec3, cam_warp_t: f32,
+    cam_follow_after_warp: bool, cam_follow_planet: Option<usize>,
+}
+
+impl Scene {
+    pub fn new(w: usize, h: usize, config: &SceneConfig) -> Result<Self, Box<dyn std::error::Error>> {
+        let viewport = create_viewport_matrix(w as f32, h as f32);
+        let aspect = w as f32 / h as f32;
+
+        let camera = FreeOrbitCamera::new(vec3(0.0, 6.0, 24.0), vec3(0.0, 0.0, 0.0));
+        // Ship 
+        let ship = Ship::new(vec3(0.0, 0.0, 26.0));
+
+        // Skybox: catalog stars at their real positions, plus the synthetic faint fill only when asked for
+        let mut sky = Skybox::load(&config.stars_path, config.synthetic_stars.then_some(12345))?;
+        sky.show_lines = config.constellations;
+
+        // Models
+        // Planets use built-in sphere LODs unless an OBJ is given. The radius matches the bundled sphere.obj (0.5)
+        // so `scale` means the same thing either way.
+        let spheres = match (&config.sphere_path, config.sphere_kind) {
+            (Some(path), _) => Lods::single(Obj::load(path)?.get_mesh()),
+            (None, SphereKind::Ico) => Lods::icospheres(0.5),
+            (None, SphereKind::Uv) => Lods::uv_spheres(0.5),
+        };
+        let ship_model = Obj::load(&config.ship_path)?.into_model();
+        let asteroid_path_try = "assets/models/Asteoid.obj".to_string();
+        let asteroid_path_fallback = "assets/models/Asteroid.obj".to_string();
+        let asteroid_mesh = Obj::load(&asteroid_path_try).or_else(|_| Obj::load(&asteroid_path_fallback))?;
+        let asteroid_model = asteroid_mesh.into_model();
+
+        let mut asteroid_max_r = 0.0f32;
+        for v in asteroid_model.vertices() { let l = v.position.magnitude(); if l > asteroid_max_r { asteroid_max_r = l; } }
+        let asteroid_unit_scale = if asteroid_max_r > 1e-6 { 1.0 / asteroid_max_r } else { 1.0 };
+
+        let system = SolarSystem::load(&config.system_path)?;
+
+        // Asteroid field (keep only a couple at once)
+        let mut rng = Lcg::new(0xC0FFEE12);
+        let max_asteroids = 2usize;
+        let asteroids: Vec<Asteroid> = (0..max_asteroids).map(|_| spawn_asteroid_crossing_ship(&ship, &mut rng)).collect();
+
+        Ok(Self {
+            viewport, aspect, base_fov_deg: 45.0, near: 0.1, far: 2000.0,
+            cam_warp_origin_eye: camera.eye, cam_warp_origin_center: camera.center,
+            camera, prev_ship: ship.clone(), ship, sky, bloom: Bloom::new(config.bloom),
+            spheres, ship_model, asteroid_model, asteroid_unit_scale,
+            asteroid_noises: [create_noise_fbmn(7, 1.0, 5), create_noise_fbmn(8, 3.0, 3)],
+            system,
+            clock: SimClock::new(config.start_time), date_origin: config.start_date.map(|jd| (jd, config.start_time)), physics: FixedStep::default(),
+            rng, asteroids, max_asteroids,
+            cam_detached: false, cam_warp_target: None, cam_warp_t: 0.0,
+            cam_follow_after_warp: false, cam_follow_planet: None,
+        })
+    }
+
+    // Julian day of the simulation clock in ephemeris mode
+    pub fn julian_day(&self) -> Option<f64> { self.date_origin.map(|(jd, secs)| jd + (self.clock.seconds - secs) / 86_400.0) }
+
+    pub fn sim_time(&self) -> SimTime { SimTime { tsec: self.clock.seconds, jd: self.julian_day() } }
+
+    // Current date (if any) and time warp, for the window title
+    pub fn status_line(&self) -> String {
+        match self.julian_day() { Some(jd) => format!("{} - {}", format_date(jd), self.clock.status()), None => self.clock.status() }
+    }
+
+    fn update_clock(&mut self, input: &Input, real_dt: f32) {
+        // Time warp: O pauses, T reverses, - and = change speed by 10x, Backspace resets to 1x
+        if input.is_key_pressed(Key::O) { self.clock.toggle_pause(); }
+        if input.is_key_pressed(Key::T) { self.clock.toggle_reverse(); }
+        if input.is_key_pressed(Key::Minus) { self.clock.slower(); }
+        if input.is_key_pressed(Key::Equal) { self.clock.faster(); }
+        if input.is_key_pressed(Key::Backspace) { self.clock.reset_speed(); }
+        self.clock.advance(real_dt as f64);
+        if self.date_origin.is_some() {
+            // Step the calendar: , and . move one day, [ and ] move 30 days
+            let day = 86_400.0;
+            if input.is_key_pressed(Key::Comma) { self.clock.seconds -= day; }
+            if input.is_key_pressed(Key::Period) { self.clock.seconds += day; }
+            if input.is_key_pressed(Key::LeftBracket) { self.clock.seconds -= 30.0 * day; }
+            if input.is_key_pressed(Key::RightBracket) { self.clock.seconds += 30.0 * day; }
+        }
+    }
+
+    // Spin angle for a body rotating `rate` times as fast as the base 0.625 rad per simulated second
+    fn spin(&self, rate: f32) -> f32 { (self.clock.seconds * 0.625 * rate as f64).rem_euclid(std::f64::consts::TAU) as f32 }
+
+    // One fixed physics step: ship controls, collisions with the bodies and asteroid motion
+    fn tick(&mut self, input: &Input) {
+        self.prev_ship = self.ship.clone();
+        self.ship.update_controls(input);
+        let t = self.sim_time();
+        for (i, body) in self.system.bodies.iter().enumerate() {
+            let margin = if body.shader == ShaderKind::Star { 1.2 } else { 0.6 };
+            clamp_ship_sphere(&mut self.ship, self.system.position(i, t), body.scale, margin);
+        }
+
+        let (fwd, _right, _up) = self.ship.axes();
+        for a in self.asteroids.iter_mut() {
+            a.prev_pos = a.pos; a.prev_rot_y = a.rot_y;
+            if !a.alive { continue; }
+            if !a.exploding {
+                let d = (self.ship.pos - a.pos).magnitude();
+                let trigger = (a.scale * 6.0).clamp(1.0, 8.0);
+                if d < trigger { a.exploding = true; a.t = 0.0; }
+            }
+            if a.exploding {
+                a.t += FIXED_DT;
+                if a.t >= 1.0 { a.alive = false; }
+                continue;
+            }
+            a.rot_y += 0.004;
+            a.pos += a.vel;
+
+            let dist = (a.pos).magnitude();
+            let passed_ship = (a.pos - self.ship.pos).dot(&fwd) < -120.0;
+            if dist > 500.0 || a.pos.y.abs() > 80.0 || passed_ship { a.alive = false; }
+        }
+
+        // Keep at most two asteroids alive; respawn replacements when they disappear
+        self.asteroids.retain(|a| a.alive || a.exploding);
+        let mut alive_count = self.asteroids.iter().filter(|a| a.alive).count();
+        while alive_count < self.max_asteroids {
+            self.asteroids.push(spawn_asteroid_crossing_ship(&self.ship, &mut self.rng));
+            alive_count += 1;
+        }
+    }
+
+    // Bodies and rings that can shadow each other (stars don't: they're the light)
+    fn occluders(&self, t: SimTime) -> Vec<Occluder> {
+        let mut out = Vec::new();
+        for (i, body) in self.system.bodies.iter().enumerate() {
+            if body.light.is_some() { continue; }
+            let center = self.system.position(i, t);
+            out.push(Occluder::Sphere { center, radius: body.scale * 0.5 });
+            if let Some(ring) = &body.rings {
+                let inv_model = create_model_matrix(center, body.scale, self.spin(0.2)).try_inverse().unwrap_or_else(Mat4::identity);
+                out.push(Occluder::Ring { inv_model, ring: *ring });
+            }
+        }
+        out
+    }
+
+    // Advances the simulation by `real_dt` seconds of wall-clock time and draws it into `fb`
+    pub fn frame(&mut self, fb: &mut Framebuffer, input: &Input, real_dt: f32) {
+        self.update_clock(input, real_dt); fb.clear(0x000000);
+        // L toggles the constellation lines
+        if input.is_key_pressed(Key::L) { self.sky.show_lines = !self.sky.show_lines; }
+
+        for _ in 0..self.physics.ticks(real_dt) { self.tick(input); }
+        // Everything below draws the ship and asteroids interpolated between the last two ticks
+        let alpha = self.physics.alpha();
+        let ship = self.prev_ship.interpolate(&self.ship, alpha);
+
+        let keys = [Key::Key0, Key::Key1, Key::Key2, Key::Key3, Key::Key4, Key::Key5, Key::Key6, Key::Key7, Key::Key8, Key::Key9];
+        let selected = self.system.primaries().zip(keys).find(|(_, key)| input.is_key_pressed(*key)).map(|(i, _)| i);
+        if let Some(i) = selected {
+            let s = self.system.bodies[i].scale;
+            let center_t = self.system.position(i, self.sim_time());
+            let eye_offset = vec3(0.0, s*2.5 + 4.0, s*3.5 + 7.0);
+            let eye_t = center_t + eye_offset;
+            self.cam_detached = true; self.cam_follow_after_warp = false; self.camera.up = vec3(0.0,1.0,0.0);
+            self.cam_follow_planet = Some(i);
+            self.cam_warp_origin_eye = self.camera.eye; self.cam_warp_origin_center = self.camera.center; self.cam_warp_target = Some((eye_t, center_t)); self.cam_warp_t = 0.0;
+        }
+
+        let (fwd, _right, up_axis) = ship.axes();
+        let cam_dist = 6.0; let cam_height = 2.2; let lookahead = 6.0;
+        let follow_eye = ship.pos - fwd*cam_dist + up_axis*cam_height;
+        let follow_center = ship.pos + fwd*lookahead;
+        if input.is_key_pressed(Key::C) {
+            if self.cam_detached { self.cam_warp_origin_eye = self.camera.eye; self.cam_warp_origin_center = self.camera.center; self.cam_warp_target = Some((follow_eye, follow_center)); self.cam_warp_t = 0.0; self.cam_follow_after_warp = true; }
+            self.cam_follow_planet = None; // switch to following the ship after warp
+        }
+
+        if let Some((eye_t, center_t)) = self.cam_warp_target {
+            // 0.08 per 16 ms, in real time so the warp lasts the same at any frame rate
+            self.cam_warp_t += real_dt * 5.0; let t = self.cam_warp_t.min(1.0);
+            let u = ease_in_out_cubic(t);
+            let world_up = vec3(0.0,1.0,0.0);
+
+            let eye_start = self.cam_warp_origin_eye; let eye_end = eye_t;
+            let dir = eye_end - eye_start;
+            let dist = dir.magnitude().max(1e-3);
+            let dir_n = dir / dist;
+            let mut right = dir_n.cross(&world_up);
+            if right.magnitude() < 1e-3 { right = vec3(1.0,0.0,0.0); } else { right = right.normalize(); }
+            let sign = if (eye_start.x + eye_end.z).sin() >= 0.0 { 1.0 } else { -1.0 };
+            let amp = (dist * 0.25).clamp(5.0, 40.0);
+            let eye_c1 = eye_start + dir_n * (amp*0.30) + right * (amp * 1.00 * sign);
+            let eye_c2 = eye_end   - dir_n * (amp*0.30) + right * (amp * 0.60 * sign);
+            let eye_pos = bezier3(eye_start, eye_c1, eye_c2, eye_end, u);
+
+            let cen_start = self.cam_warp_origin_center; let cen_end = center_t;
+            let cdir = cen_end - cen_start;
+            let cdist = cdir.magnitude().max(1e-3);
+            let cdir_n = cdir / cdist;
+            let mut cright = cdir_n.cross(&world_up);
+            if cright.magnitude() < 1e-3 { cright = vec3(1.0,0.0,0.0); } else { cright = cright.normalize(); }
+            let camp = (cdist * 0.20).clamp(3.0, 25.0);
+            let cen_c1 = cen_start + cdir_n * (camp*0.35) + cright * (camp * 0.6 * sign);
+            let cen_c2 = cen_end   - cdir_n * (camp*0.35) + cright * (camp * 0.4 * sign);
+            let cen_pos = bezier3(cen_start, cen_c1, cen_c2, cen_end, u);
+
+
+            let look = (cen_pos - eye_pos).normalize();
+            let roll = (std::f32::consts::PI * u).sin() * 0.4 * sign;
+            let up = rotate_around_axis(world_up, look, roll).normalize();
+
+            self.camera.eye = eye_pos; self.camera.center = cen_pos; self.camera.up = up;
+            if self.cam_warp_t >= 1.0 { self.cam_warp_target = None; if self.cam_follow_after_warp { self.cam_detached = false; self.cam_follow_after_warp = false; } }
+        } else if !self.cam_detached {
+            self.camera.eye = follow_eye; self.camera.center = follow_center; self.camera.up = vec3(0.0,1.0,0.0);
+        } else if let Some(pi) = self.cam_follow_planet {
+            // Follow the currently selected planet
+            if pi < self.system.bodies.len() {
+                let size = self.system.bodies[pi].scale;
+                let center_t = self.system.position(pi, self.sim_time());
+                let eye_offset = vec3(0.0, size*2.5 + 4.0, size*3.5 + 7.0);
+                self.camera.eye = center_t + eye_offset; self.camera.center = center_t; self.camera.up = vec3(0.0,1.0,0.0);
+            }
+        }
+
+
+        let fov_deg = if self.cam_warp_target.is_some() {
+            let t = self.cam_warp_t.min(1.0);
+            let u = ease_in_out_cubic(t);
+            let bell = (u * (1.0 - u)) * 4.0; // 0..1..0
+            let overshoot = (ease_out_back(u) - u).max(0.0);
+            self.base_fov_deg + 28.0 * bell + 6.0 * overshoot
+        } else { self.base_fov_deg };
+        let projection = nalgebra_glm::perspective(self.aspect, fov_deg.to_radians(), self.near, self.far);
+        let view = self.camera.view_matrix();
+        let viewport = self.viewport;
+        let time = self.clock.millis();
+        self.sky.render(fb, &view, &projection, &viewport);
+
+        let sim_time = self.sim_time();
+        let light = self.system.light(sim_time);
+        let occluders = self.occluders(sim_time);
+
+        let mut translucent: Vec<(f32, Translucent)> = Vec::new();
+        for (i, body) in self.system.bodies.iter().enumerate() {
+            let pos = self.system.position(i, sim_time);
+            let spin = self.spin(body.spin);
+            let u = Uniforms { model_matrix: create_model_matrix(pos, body.scale, spin), view_matrix: view, projection_matrix: projection, viewport_matrix: viewport, time, noises: body.noises.iter().collect(), camera_pos: self.camera.eye, texture: body.texture.as_ref(), light, occluders: &occluders };
+
+            // Stars are always drawn in full; other bodies fall back to flat shading when tiny.
+            // A center that can't be projected is either far behind the camera or right next to it.
+            let radius_px = screen_radius_px(&view, &projection, &viewport, pos, body.scale)
+                .unwrap_or(if (self.camera.eye - pos).magnitude() < body.scale * 4.0 { f32::INFINITY } else { 0.0 });
+            let mesh = self.spheres.select(radius_px);
+            let dist = (self.camera.eye - pos).magnitude();
+            fb.set_emission(body.emission);
+            if body.shader == ShaderKind::Star {
+                render(fb, &u, mesh, |frag| shaders::fragment_body(body.shader, body.color, frag, &u));
+            } else if radius_px < 2.0 {
+                continue;
+            } else if radius_px < 7.0 {
+                let base = body.color;
+                render(fb, &u, mesh, |frag| lambert(base, frag, &u));
+            } else {
+                render(fb, &u, mesh, |frag| shaders::fragment_body(body.shader, body.color, frag, &u));
+                // Inner layers first so they stay under the outer ones when the distances tie
+                if body.clouds.is_some() { translucent.push((dist, Translucent::Clouds { body: i, pos, radius_px })); }
+                if body.atmosphere.is_some() { translucent.push((dist, Translucent::Atmosphere { body: i, pos, radius_px })); }
+            }
+            if body.rings.is_some() { translucent.push((dist, Translucent::Rings { body: i, pos, radius_px })); }
+        }
+        fb.set_emission(0.0);
+
+        // --- Asteroids render ---
+        for a in &self.asteroids {
+            if !a.alive { continue; }
+            let pos = a.prev_pos.lerp(&a.pos, alpha);
+            if a.exploding {
+                translucent.push(((self.camera.eye - pos).magnitude(), Translucent::Explosion { pos, scale: a.scale, t: a.t.min(1.0) }));
+                continue;
+            }
+            let rot_y = a.prev_rot_y + (a.rot_y - a.prev_rot_y) * alpha;
+            let model = create_model_matrix(pos, a.scale * self.asteroid_unit_scale, rot_y);
+            let u = Uniforms { model_matrix: model, view_matrix: view, projection_matrix: projection, viewport_matrix: viewport, time, noises: self.asteroid_noises.iter().collect(), camera_pos: self.camera.eye, texture: None, light, occluders: &occluders };
+
+            for part in &self.asteroid_model.parts {
+                let m = &self.asteroid_model.materials[part.material];
+                render(fb, &u, &part.mesh, |frag| shaders::fragment_asteroid(frag, &u, Some(m)));
+            }
+        }
+
+        let ship_rot_y = ship.yaw + std::f32::consts::FRAC_PI_2;
+        let u = Uniforms { model_matrix: create_model_matrix_euler(ship.pos, 0.25, -ship.pitch, ship_rot_y, ship.roll), view_matrix: view, projection_matrix: projection, viewport_matrix: viewport, time, noises: vec![], camera_pos: self.camera.eye, texture: None, light, occluders: &occluders };
+        for part in &self.ship_model.parts {
+            let m = &self.ship_model.materials[part.material];
+            fb.set_emission(m.emission_strength());
+            render(fb, &u, &part.mesh, |frag| shaders::fragment_material(m, frag, &u));
+        }
+        fb.set_emission(0.0);
+
+        draw_orbit_trails(fb, &view, &projection, &viewport, &self.system, sim_time);
+
+        // Transparent pass: back to front over the finished opaque scene, depth-tested but not written.
+        // The sort is stable, so layers of one body keep the order they were queued in
+        translucent.sort_by(|a, b| b.0.total_cmp(&a.0));
+        for (_, item) in &translucent {
+            match *item {
+                Translucent::Clouds { body, pos, radius_px } => {
+                    let b = &self.system.bodies[body]; let Some(clouds) = &b.clouds else { continue };
+                    let u = Uniforms { model_matrix: create_model_matrix(pos, b.scale * (1.0 + clouds.height), self.spin(b.spin + clouds.drift)), view_matrix: view, projection_matrix: projection, viewport_matrix: viewport, time, noises: vec![], camera_pos: self.camera.eye, texture: None, light, occluders: &occluders };
+                    fb.set_blend(Blend::Alpha);
+                    render(fb, &u, self.spheres.select(radius_px), |frag| shaders::fragment_clouds(clouds, frag, &u));
+                }
+                Translucent::Atmosphere { body, pos, radius_px } => {
+                    let b = &self.system.bodies[body]; let Some(atm) = &b.atmosphere else { continue };
+                    let shell = b.scale * (1.0 + atm.thickness);
+                    let u = Uniforms { model_matrix: create_model_matrix(pos, shell, 0.0), view_matrix: view, projection_matrix: projection, viewport_matrix: viewport, time, noises: vec![], camera_pos: self.camera.eye, texture: None, light, occluders: &occluders };
+                    fb.set_blend(Blend::Additive);
+                    render(fb, &u, self.spheres.select(radius_px * (1.0 + atm.thickness)), |frag| shaders::fragment_atmosphere(atm, frag, &u));
+                }
+                Translucent::Rings { body, pos, radius_px } => {
+                    let b = &self.system.bodies[body]; let Some(ring) = &b.rings else { continue };
+                    let segs = if radius_px < 12.0 { 32 } else if radius_px < 40.0 { 64 } else { 128 };
+                    fb.set_blend(Blend::Alpha);
+                    render_ring_with_segments(fb, &view, &projection, &viewport, &light, &occluders, pos, b.scale, self.spin(0.2), ring, segs);
+                }
+                Translucent::Explosion { pos, scale, t } => {
+                    // Expanding fireball that fades out; its glow comes from bloom
+                    let scale = scale * (1.0 + 3.0 * t);
+                    let u = Uniforms { model_matrix: create_model_matrix(pos, scale, 0.0), view_matrix: view, projection_matrix: projection, viewport_matrix: viewport, time, noises: vec![], camera_pos: self.camera.eye, texture: None, light, occluders: &occluders };
+                    let radius_px = screen_radius_px(&view, &projection, &viewport, pos, scale).unwrap_or(0.0);
+                    fb.set_blend(Blend::Alpha); fb.set_emission(4.0 * (1.0 - t));
+                    render(fb, &u, self.spheres.select(radius_px), |frag| shaders::fragment_explosion(frag, &u, t));
+                    fb.set_emission(0.0);
+                }
+            }
+        }
+        fb.set_blend(Blend::Replace);
+
+        self.bloom.apply(fb);
+        fb.resolve();
+    }
+}
+
+// Trails fade from the body back along its orbit; they're drawn after the opaque geometry so planets hide them
+pub fn draw_orbit_trails(fb: &mut Framebuffer, view: &Mat4, proj: &Mat4, vp: &Mat4, system: &SolarSystem, t: SimTime) {
+    for body in &system.bodies {
+        // Moons move with their parent, so a trail in world space would smear; only primaries get one, and those
+        // orbit the origin (see `SolarSystem::position`)
+        let Some((orbit, m_now)) = body.orbit_at(t) else { continue };
+        if body.parent.is_some() { continue; }
+        let segments = 72;
+        let tail_len = 1.4;
+        let step = tail_len / segments as f32;
+        let base = Color::new(110,110,110);
+        let points: Vec<Vec3> = (0..=segments).map(|i| orbit.position_at_mean_anomaly(m_now - i as f32 * step)).collect();
+        draw_polyline_world(fb, view, proj, vp, &points, 1.2, |i| scale_color(base, 0.35 + 0.65 * (1.0 - i as f32 / segments as f32)));
+    }
+}
@@ -1,27 +1,169 @@
-use crate::{color::Color, fragment::Fragment, vertex::Vertex, Uniforms};
-use nalgebra_glm::{mat4_to_mat3, Vec3, Vec4, Mat3};
+use crate::{clipping::to_screen, color::{Color, Rgba}, material::Material, shadow::transmittance, texture::{spherical_uv, Mapping}, fragment::Fragment, system::{AtmosphereDesc, Clouds, RingDesc, ShaderKind}, vertex::Vertex, Uniforms};
+use nalgebra_glm::{mat4_to_mat3, Mat3, Mat4, Vec3, Vec4};
 
-pub fn vertex_shader(vertex: &Vertex, uniforms: &Uniforms) -> Vertex {
+// Matrices shared by every vertex of a draw call, computed once per `render`
+pub struct VertexTransforms { pub mvp: Mat4, pub model3: Mat3, pub normal_matrix: Mat3, pub viewport: Mat4 }
+
+impl VertexTransforms {
+    pub fn new(uniforms: &Uniforms) -> Self {
+        let model3 = mat4_to_mat3(&uniforms.model_matrix);
+        let normal_matrix: Mat3 = model3.transpose().try_inverse().unwrap_or(Mat3::identity());
+        Self { mvp: uniforms.projection_matrix * uniforms.view_matrix * uniforms.model_matrix, model3, normal_matrix, viewport: uniforms.viewport_matrix }
+    }
+}
+
+pub fn vertex_shader(vertex: &Vertex, t: &VertexTransforms) -> Vertex {
     let pos4 = Vec4::new(vertex.position.x, vertex.position.y, vertex.position.z, 1.0);
-    let clip = uniforms.projection_matrix * uniforms.view_matrix * uniforms.model_matrix * pos4;
-    let ndc = Vec4::new(clip.x / clip.w, clip.y / clip.w, clip.z / clip.w, 1.0);
-    let screen = uniforms.viewport_matrix * ndc;
-    let model3 = mat4_to_mat3(&uniforms.model_matrix);
-    let normal_matrix: Mat3 = model3.transpose().try_inverse().unwrap_or(Mat3::identity());
-    let transformed_normal = normal_matrix * vertex.normal;
-    Vertex { position: vertex.position, normal: vertex.normal, color: vertex.color, transformed_position: Vec3::new(screen.x, screen.y, screen.z), transformed_normal }
-}
-
-pub fn lambert(base: Color, fragment: &Fragment) -> Color {
-    let light_pos = Vec3::new(0.0, 0.0, 20.0);
-    let l = (light_pos - fragment.vertex_position).normalize();
+    let clip = t.mvp * pos4;
+    let transformed_normal = t.normal_matrix * vertex.normal;
+    let transformed_tangent = t.model3 * vertex.tangent;
+    Vertex { position: vertex.position, normal: vertex.normal, tex_coords: vertex.tex_coords, tangent: vertex.tangent, color: vertex.color, clip_position: clip, transformed_position: to_screen(&clip, &t.viewport), transformed_normal, transformed_tangent }
+}
+
+// World-space position of the fragment (`vertex_position` is in model space)
+fn world_position(fragment: &Fragment, uniforms: &Uniforms) -> Vec3 {
+    let p = fragment.vertex_position;
+    let wp4 = uniforms.model_matrix * Vec4::new(p.x, p.y, p.z, 1.0);
+    Vec3::new(wp4.x, wp4.y, wp4.z)
+}
+
+// Direction to the star and the light arriving at `p`, dimmed by whatever in `uniforms.occluders` is in the way
+fn incident(uniforms: &Uniforms, p: Vec3) -> (Vec3, Vec3) {
+    let (l, light) = uniforms.light.incident(p);
+    if uniforms.occluders.is_empty() { return (l, light); }
+    (l, light * transmittance(uniforms.occluders, &uniforms.light, p))
+}
+
+// Diffuse lighting from the star in `uniforms.light`, in world space
+pub fn lambert(base: Color, fragment: &Fragment, uniforms: &Uniforms) -> Color {
+    let (l, light) = incident(uniforms, world_position(fragment, uniforms));
     let n = fragment.normal.normalize();
     let diff = n.dot(&l).max(0.0);
     let ambient = 0.2;
-    base * (ambient + diff * 0.8)
+    base * (Vec3::repeat(ambient) + light * (diff * 0.8))
+}
+
+pub fn fragment_solid(color: Color, fragment: &Fragment, uniforms: &Uniforms) -> Color { lambert(color, fragment, uniforms) }
+
+// Blinn-Phong with an MTL material (Ka/Kd/Ks/Ns, diffuse and normal maps), lit by the star
+pub fn fragment_material(material: &Material, fragment: &Fragment, uniforms: &Uniforms) -> Color {
+    let world_pos = world_position(fragment, uniforms);
+    let n = material.shading_normal(fragment);
+    let (l, light) = incident(uniforms, world_pos);
+    let v = (uniforms.camera_pos - world_pos).normalize();
+    let h = (l + v).normalize();
+
+    let albedo = material.albedo(fragment.tex_coords);
+    let diff = n.dot(&l).max(0.0);
+    let spec = if diff > 0.0 { n.dot(&h).max(0.0).powf(material.shininess.max(1.0)) } else { 0.0 };
+    // Ambient is kept fairly high so the ship stays readable with the Sun behind it
+    let c = material.ambient.component_mul(&albedo) * 0.25 + (albedo * diff + material.specular * spec).component_mul(&light) + material.emission;
+    Color::from_float(c.x, c.y, c.z)
 }
 
-pub fn fragment_solid(color: Color, fragment: &Fragment) -> Color { lambert(color, fragment) }
+// Distances along the ray o + t*d (d normalized) where it enters and leaves the sphere, if it hits it
+fn ray_sphere(o: Vec3, d: Vec3, c: Vec3, r: f32) -> Option<(f32, f32)> {
+    let oc = o - c; let b = oc.dot(&d); let disc = b * b - (oc.dot(&oc) - r * r);
+    if disc < 0.0 { return None; }
+    let s = disc.sqrt(); Some((-b - s, -b + s))
+}
+
+// Atmosphere shell, drawn additively over the body with a sphere `1 + thickness` times its size. The view ray
+// is marched through the shell (stopping at the surface) and each sample adds Rayleigh and Mie scattered
+// sunlight, dimmed by extinction on the way in from the star and on the way out to the camera.
+pub fn fragment_atmosphere(atm: &AtmosphereDesc, fragment: &Fragment, uniforms: &Uniforms) -> Color {
+    const STEPS: usize = 8; const SUN_STEPS: usize = 4;
+    let m = &uniforms.model_matrix;
+    let center = Vec3::new(m[(0, 3)], m[(1, 3)], m[(2, 3)]);
+    let outer = Vec3::new(m[(0, 0)], m[(1, 0)], m[(2, 0)]).magnitude() * 0.5;
+    let inner = outer / (1.0 + atm.thickness);
+    let shell = outer - inner;
+    let eye = uniforms.camera_pos;
+    let dir = (world_position(fragment, uniforms) - eye).normalize();
+    let Some((t0, mut t1)) = ray_sphere(eye, dir, center, outer) else { return Color::new(0, 0, 0) };
+    let t0 = t0.max(0.0);
+    if let Some((ts, _)) = ray_sphere(eye, dir, center, inner) { if ts > 0.0 { t1 = t1.min(ts); } }
+    if t1 <= t0 { return Color::new(0, 0, 0); }
+
+    // Coefficients per world unit: `density` 1 is an optical depth of about 0.25 straight up through the shell
+    let beta_r = Vec3::new(atm.color[0], atm.color[1], atm.color[2]) * (atm.density / shell);
+    let beta_m = atm.mie * atm.density / shell;
+    let extinction = beta_r.add_scalar(beta_m);
+    let rho = |x: Vec3| (-((x - center).magnitude() - inner).max(0.0) / (shell * 0.25)).exp();
+
+    let (l, sun) = incident(uniforms, center);
+    let mu = dir.dot(&l);
+    let phase_r = 3.0 / (16.0 * std::f32::consts::PI) * (1.0 + mu * mu);
+    let g = atm.mie_g;
+    let phase_m = (1.0 - g * g) / (4.0 * std::f32::consts::PI * (1.0 + g * g - 2.0 * g * mu).max(1e-4).powf(1.5));
+
+    let ds = (t1 - t0) / STEPS as f32;
+    let mut depth_view = 0.0;
+    let mut sum = Vec3::zeros();
+    for i in 0..STEPS {
+        let x = eye + dir * (t0 + (i as f32 + 0.5) * ds);
+        let d = rho(x) * ds;
+        depth_view += d * 0.5;
+        // Samples on the night side get no sunlight
+        if !ray_sphere(x, l, center, inner).is_some_and(|(a, _)| a > 0.0) {
+            let exit = ray_sphere(x, l, center, outer).map_or(0.0, |(_, b)| b.max(0.0));
+            let sds = exit / SUN_STEPS as f32;
+            let depth_sun: f32 = (0..SUN_STEPS).map(|j| rho(x + l * ((j as f32 + 0.5) * sds)) * sds).sum();
+            let att = (-extinction * (depth_view + depth_sun)).map(|v| v.exp());
+            sum += att.component_mul(&(beta_r * phase_r).add_scalar(beta_m * phase_m)) * d;
+        }
+        depth_view += d * 0.5;
+    }
+    let c = sum.component_mul(&sun) * 4.0 * std::f32::consts::PI;
+    Color::from_linear(c)
+}
+
+// Exploding asteroid: a fireball that cools from white-yellow to deep orange over `t` in 0..1, brighter and
+// more opaque where it faces the camera so the edge stays soft. Drawn emissive so the bloom pass gives it its glow.
+pub fn fragment_explosion(fragment: &Fragment, uniforms: &Uniforms, t: f32) -> Rgba {
+    let v = (uniforms.camera_pos - world_position(fragment, uniforms)).normalize();
+    let facing = fragment.normal.normalize().dot(&v).max(0.0);
+    let hot = Color::new(255, 240, 200); let cool = Color::new(255, 110, 30);
+    lerp_color(cool, hot, (1.0 - t) * (0.4 + 0.6 * facing)).with_alpha(((1.0 - t) * (0.3 + 0.7 * facing) * 1.5).min(1.0))
+}
+
+// Cloud shell over a body: white where the noise clears `coverage`, transparent elsewhere, lit like the surface
+pub fn fragment_clouds(clouds: &Clouds, fragment: &Fragment, uniforms: &Uniforms) -> Rgba {
+    let p = fragment.vertex_position * 4.0;
+    let n = (clouds.noise.get_noise_3d(p.x, p.y, p.z) + 1.0) * 0.5;
+    let c = smoothstep(clouds.coverage, clouds.coverage + 0.2, n);
+    lambert(Color::new(255, 255, 255), fragment, uniforms).with_alpha(c * clouds.opacity)
+}
+
+// Bitmap from `uniforms.texture` at the fragment's UV, lit like `fragment_solid`; falls back to `color` without a texture
+pub fn fragment_textured(color: Color, fragment: &Fragment, uniforms: &Uniforms) -> Color {
+    let albedo = uniforms.texture.map_or(color, |t| match t.mapping {
+        Mapping::Uv => t.sample(fragment.tex_coords),
+        Mapping::Spherical => t.sample(spherical_uv(fragment.vertex_position)),
+    });
+    lambert(albedo, fragment, uniforms)
+}
+
+// Dispatch for data-driven bodies; `base` is the body's flat color
+pub fn fragment_body(kind: ShaderKind, base: Color, fragment: &Fragment, uniforms: &Uniforms) -> Color {
+    match kind {
+        ShaderKind::Star => fragment_star(fragment, uniforms),
+        ShaderKind::Mercury => fragment_mercury(fragment, uniforms),
+        ShaderKind::Venus => fragment_venus(fragment, uniforms),
+        ShaderKind::Earth => fragment_earth(fragment, uniforms),
+        ShaderKind::Mars => fragment_mars(fragment, uniforms),
+        ShaderKind::Jupiter => fragment_jupiter(fragment, uniforms),
+        ShaderKind::Saturn => fragment_saturn(fragment, uniforms),
+        ShaderKind::Uranus => fragment_uranus(fragment, uniforms),
+        ShaderKind::Neptune => fragment_neptune(fragment, uniforms),
+        ShaderKind::Moon => fragment_moon(fragment, uniforms),
+        ShaderKind::Rocky => fragment_rocky(fragment, uniforms),
+        ShaderKind::Gas => fragment_gas(fragment, uniforms),
+        ShaderKind::Asteroid => fragment_asteroid(fragment, uniforms, None),
+        ShaderKind::Solid => fragment_solid(base, fragment, uniforms),
+        ShaderKind::Textured => fragment_textured(base, fragment, uniforms),
+    }
+}
 
 // Helpers
 fn lerp_color(a: Color, b: Color, t: f32) -> Color {
@@ -33,7 +175,7 @@ fn lerp_color(a: Color, b: Color, t: f32) -> Color {
     }
 }
 
-fn smoothstep(edge0: f32, edge1: f32, x: f32) -> f32 {
+pub fn smoothstep(edge0: f32, edge1: f32, x: f32) -> f32 {
     let t = ((x - edge0) / (edge1 - edge0)).clamp(0.0, 1.0);
     t * t * (3.0 - 2.0 * t)
 }
@@ -158,7 +300,7 @@ pub fn fragment_rocky(fragment: &Fragment, uniforms: &Uniforms) -> Color {
             col = lerp_color(col, Color::from_float(1.0, 1.0, 1.0), c * 0.20);
         }
     }
-    lambert(col, fragment)
+    lambert(col, fragment, uniforms)
 }
 
 pub fn fragment_mercury(fragment: &Fragment, uniforms: &Uniforms) -> Color {
@@ -177,7 +319,7 @@ pub fn fragment_mercury(fragment: &Fragment, uniforms: &Uniforms) -> Color {
     // Bright ejecta
     let ejecta = if uniforms.noises.len() > 3 { ((uniforms.noises[3].get_noise_3d(p.x * 6.0, p.y * 6.0, p.z * 6.0) + 1.0) * 0.5).clamp(0.0, 1.0) } else { 0.0 };
     col = lerp_color(col, Color::from_float(0.82, 0.78, 0.70), ejecta * 0.12);
-    lambert(col, fragment)
+    lambert(col, fragment, uniforms)
 }
 
 pub fn fragment_venus(fragment: &Fragment, uniforms: &Uniforms) -> Color {
@@ -194,7 +336,7 @@ pub fn fragment_venus(fragment: &Fragment, uniforms: &Uniforms) -> Color {
     // High-altitude haze
     let haze = uniforms.noises[1].get_noise_3d(p.x * 1.2, p.y * 1.2, p.z * 1.2);
     base = lerp_color(base, Color::from_float(1.0, 0.98, 0.92), saturate((haze + 1.0) * 0.5) * 0.12);
-    lambert(base, fragment)
+    lambert(base, fragment, uniforms)
 }
 
 pub fn fragment_earth(fragment: &Fragment, uniforms: &Uniforms) -> Color {
@@ -215,7 +357,7 @@ pub fn fragment_earth(fragment: &Fragment, uniforms: &Uniforms) -> Color {
         col = lerp_color(land_biome, mountain, m_fac);
         if uniforms.noises.len() > 3 { let n_cloud = uniforms.noises[3].get_noise_3d(p.x * 4.0, p.y * 4.0, p.z * 4.0); let c = smoothstep(0.55, 0.75, (n_cloud + 1.0) * 0.5); col = lerp_color(col, Color::from_float(1.0, 1.0, 1.0), c * 0.22); }
     }
-    lambert(col, fragment)
+    lambert(col, fragment, uniforms)
 }
 
 pub fn fragment_mars(fragment: &Fragment, uniforms: &Uniforms) -> Color {
@@ -236,7 +378,7 @@ pub fn fragment_mars(fragment: &Fragment, uniforms: &Uniforms) -> Color {
     // Dust storms
     let storms = if uniforms.noises.len() > 3 { uniforms.noises[3].get_noise_3d(p.x * 1.4, p.y * 1.4, p.z * 1.4) } else { 0.0 };
     let s = smoothstep(0.55, 0.8, (storms + 1.0) * 0.5); col = lerp_color(col, Color::from_float(0.94, 0.78, 0.60), s * 0.22);
-    lambert(col, fragment)
+    lambert(col, fragment, uniforms)
 }
 
 pub fn fragment_gas(fragment: &Fragment, uniforms: &Uniforms) -> Color {
@@ -248,7 +390,7 @@ pub fn fragment_gas(fragment: &Fragment, uniforms: &Uniforms) -> Color {
     let fine = ((p.y * 24.0 + uniforms.noises[0].get_noise_3d(p.x * 0.5, p.y * 0.5, p.z * 0.5) * 0.6).sin() + 1.0) * 0.5; col = lerp_color(col, Color::from_float(1.0, 0.96, 0.88), fine * 0.18);
     let d = ((uniforms.noises[1].get_noise_3d(p.x * 1.7, p.y * 1.4, p.z * 1.6) + 1.0) * 0.5).clamp(0.0, 1.0); col = lerp_color(col, Color::from_float(1.0, 1.0, 1.0), d * 0.12);
     if uniforms.noises.len() > 2 { let s = uniforms.noises[2].get_noise_3d(p.x * 0.9 + 1.3, p.y * 0.7 - 0.7, p.z * 0.9); let mask = smoothstep(0.5, 0.8, s.abs()); col = lerp_color(col, Color::from_float(0.30, 0.27, 0.25), mask * 0.45); }
-    lambert(col, fragment)
+    lambert(col, fragment, uniforms)
 }
 
 pub fn fragment_jupiter(fragment: &Fragment, uniforms: &Uniforms) -> Color {
@@ -282,7 +424,7 @@ pub fn fragment_jupiter(fragment: &Fragment, uniforms: &Uniforms) -> Color {
     col = lerp_color(col, Color::from_float(0.86, 0.40, 0.18), core * 0.9);
     col = lerp_color(col, Color::from_float(1.0, 0.94, 0.88), halo * 0.25);
 
-    lambert(col, fragment)
+    lambert(col, fragment, uniforms)
 }
 
 pub fn fragment_saturn(fragment: &Fragment, uniforms: &Uniforms) -> Color {
@@ -301,7 +443,7 @@ pub fn fragment_saturn(fragment: &Fragment, uniforms: &Uniforms) -> Color {
     // Soft haze
     let haze = ((uniforms.noises[1].get_noise_3d(p.x * 1.1, p.y * 1.1, p.z * 1.1) + 1.0) * 0.5).clamp(0.0, 1.0);
     col = lerp_color(col, Color::from_float(1.0, 0.98, 0.90), haze * 0.10);
-    lambert(col, fragment)
+    lambert(col, fragment, uniforms)
 }
 
 pub fn fragment_uranus(fragment: &Fragment, uniforms: &Uniforms) -> Color {
@@ -316,7 +458,7 @@ pub fn fragment_uranus(fragment: &Fragment, uniforms: &Uniforms) -> Color {
     // Faint polar brightening
     let polar = lat0.abs();
     col = lerp_color(col, Color::from_float(0.85, 0.98, 1.0), smoothstep(1.1, 1.5, polar) * 0.12);
-    lambert(col, fragment)
+    lambert(col, fragment, uniforms)
 }
 
 pub fn fragment_neptune(fragment: &Fragment, uniforms: &Uniforms) -> Color {
@@ -335,7 +477,7 @@ pub fn fragment_neptune(fragment: &Fragment, uniforms: &Uniforms) -> Color {
     let rim  = (1.0 - smoothstep(1.0, 1.3, e)).clamp(0.0, 1.0);
     col = lerp_color(col, Color::from_float(0.02, 0.10, 0.28), core * 0.9);
     col = lerp_color(col, Color::from_float(0.85, 0.95, 1.0), rim * 0.20);
-    lambert(col, fragment)
+    lambert(col, fragment, uniforms)
 }
 
 pub fn fragment_moon(fragment: &Fragment, uniforms: &Uniforms) -> Color {
@@ -344,18 +486,16 @@ pub fn fragment_moon(fragment: &Fragment, uniforms: &Uniforms) -> Color {
     let n = if !uniforms.noises.is_empty() { let v = uniforms.noises[0].get_noise_3d(p.x * 1.2, p.y * 1.2, p.z * 1.2); ((v + 1.0) * 0.5).clamp(0.0, 1.0) } else { n1 };
     let base = Color::from_float(0.65, 0.65, 0.67); let dark = Color::from_float(0.25, 0.25, 0.27);
     let col = lerp_color(dark, base, n);
-    lambert(col, fragment)
+    lambert(col, fragment, uniforms)
 }
 
 // Distinctive small-body shader for asteroids: dark rocky base with glints and phase brightening
-pub fn fragment_asteroid(fragment: &Fragment, uniforms: &Uniforms) -> Color {
+pub fn fragment_asteroid(fragment: &Fragment, uniforms: &Uniforms, material: Option<&Material>) -> Color {
     let p = fragment.vertex_position;
-    // World position for lighting wrt the Sun at origin
-    let wp4 = uniforms.model_matrix * Vec4::new(p.x, p.y, p.z, 1.0);
-    let world_pos = Vec3::new(wp4.x, wp4.y, wp4.z);
+    let world_pos = world_position(fragment, uniforms);
 
     let n = fragment.normal.normalize();
-    let l = (-world_pos).normalize(); // light from Sun at origin
+    let (l, light) = incident(uniforms, world_pos);
     let v = (uniforms.camera_pos - world_pos).normalize();
 
     // Albedo: dark carbonaceous rock with mottling
@@ -364,7 +504,13 @@ pub fn fragment_asteroid(fragment: &Fragment, uniforms: &Uniforms) -> Color {
     let h = ((base_n * 0.7 + detail_n * 0.3) + 1.0) * 0.5;
     let dark = Color::from_float(0.16, 0.16, 0.18);
     let mid  = Color::from_float(0.28, 0.28, 0.30);
-    let mut col = lerp_color(dark, mid, (h * 1.1).clamp(0.0, 1.0));
+    let col = lerp_color(dark, mid, (h * 1.1).clamp(0.0, 1.0));
+    let col = match material {
+        // With a material the rock is tinted by its albedo relative to its own Kd, so a plain Kd keeps the procedural look
+        // and map_Kd adds the texture's detail whatever Kd the MTL file gives
+        Some(m) => { let a = m.albedo(fragment.tex_coords).component_div(&m.diffuse.map(|k| k.max(1e-3))); Color::from_float(col.r as f32 / 255.0 * a.x, col.g as f32 / 255.0 * a.y, col.b as f32 / 255.0 * a.z) }
+        None => col,
+    };
 
     // Lambert diffuse
     let diff = n.dot(&l).max(0.0);
@@ -382,14 +528,21 @@ pub fn fragment_asteroid(fragment: &Fragment, uniforms: &Uniforms) -> Color {
     // Rim light when backlit
     let rim = (1.0 - n.dot(&v).clamp(0.0, 1.0)).powf(2.0) * (0.4 * (1.0 - diff));
 
-    let light = ambient + diff * 0.9 + spec + opposition + rim;
-    col * light.clamp(0.0, 1.6)
+    let lit = (diff * 0.9 + spec + opposition + rim).clamp(0.0, 1.6);
+    col * (Vec3::repeat(ambient) + light * lit)
 }
 
-pub fn fragment_ring(fragment: &Fragment, _uniforms: &Uniforms) -> Color {
-    let p = fragment.vertex_position;
-    let r = (p.x * p.x + (p.z / 1.2) * (p.z / 1.2)).sqrt();
-    let r_norm = ((r - 3.4) / (6.1 - 3.4)).clamp(0.0, 1.0);
+// Radius of a model-space point on the ring plane with the ellipse undone, and where it falls between the
+// ring's inner (0) and outer (1) edges. Model space is in units of the body's `scale` (diameter)
+pub fn ring_radius(p: Vec3, ring: &RingDesc) -> (f32, f32) {
+    let r = (p.x * p.x + (p.z / ring.ellipse) * (p.z / ring.ellipse)).sqrt();
+    (r, ((r - ring.inner) / (ring.outer - ring.inner).max(1e-6)).clamp(0.0, 1.0))
+}
+
+// Ring opacity profile (C, B and A rings, Cassini division and fine ringlets) at a model-space point,
+// stretched over the ring's own radii. Also used for the shadow the ring casts.
+pub fn ring_density(p: Vec3, ring: &RingDesc) -> f32 {
+    let (r, r_norm) = ring_radius(p, ring);
 
     let c_ring = (-((r_norm - 0.18).powi(2)) / (2.0 * 0.05 * 0.05)).exp();
     let b_ring = (-((r_norm - 0.52).powi(2)) / (2.0 * 0.09 * 0.09)).exp();
@@ -397,18 +550,26 @@ pub fn fragment_ring(fragment: &Fragment, _uniforms: &Uniforms) -> Color {
     let cassini = smoothstep(0.58, 0.68, r_norm) * (1.0 - smoothstep(0.68, 0.74, r_norm));
     let density = (c_ring * 0.5 + b_ring * 1.2 + a_ring * 0.9) * (1.0 - cassini * 0.8);
 
-    let fine = (r * 40.0).sin() * 0.04 + (r * 95.0).sin() * 0.02;
-    let dens = (density + fine).clamp(0.0, 1.0);
+    let fine = (r * 120.0).sin() * 0.04 + (r * 285.0).sin() * 0.02;
+    (density + fine).clamp(0.0, 1.0)
+}
 
+// How much of what's behind the ring it blocks, for both the blended ring and the shadows it casts
+pub fn ring_opacity(p: Vec3, ring: &RingDesc) -> f32 { 0.85 * ring_density(p, ring) }
+
+pub fn fragment_ring(fragment: &Fragment, uniforms: &Uniforms, ring: &RingDesc) -> Rgba {
+    let p = fragment.vertex_position;
+    let (_, r_norm) = ring_radius(p, ring);
+    let dens = ring_density(p, ring);
 
     let tint_inner = Color::from_float(0.80, 0.76, 0.68);
     let tint_outer = Color::from_float(0.65, 0.60, 0.54);
     let mut col = lerp_color(tint_inner, tint_outer, r_norm);
     col = lerp_color(Color::from_float(0.45, 0.42, 0.38), col, dens * 0.8);
 
-
+    // The ring scatters light through to its unlit face, so both sides take |n.l|
     let n = fragment.normal.normalize();
-    let l = Vec3::new(0.0, 0.0, 1.0);
+    let (l, light) = incident(uniforms, world_position(fragment, uniforms));
     let diff = n.dot(&l).abs().max(0.15);
-    col * (0.25 + diff * 0.75)
+    (col * (Vec3::repeat(0.25) + light * (diff * 0.75))).with_alpha(ring_opacity(p, ring))
 }
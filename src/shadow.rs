@@ -0,0 +1,53 @@
+use crate::{light::PointLight, shaders::{ring_opacity, ring_radius}, system::RingDesc};
+use nalgebra_glm::{Mat4, Vec3, Vec4};
+
+// Something between a surface and the star. Spheres are the bodies; rings are tested in their own model
+// space (the annulus on the y = 0 plane, radii in units of the planet scale like `RingDesc`).
+#[derive(Clone, Copy, Debug)]
+pub enum Occluder {
+    Sphere { center: Vec3, radius: f32 },
+    Ring { inv_model: Mat4, ring: RingDesc },
+}
+
+// Fraction of the star's light that reaches `p` past `occluders`. The star is a disk of `light.radius`,
+// so spheres cast an umbra and a penumbra (or an annular eclipse when they look smaller than the star)
+pub fn transmittance(occluders: &[Occluder], light: &PointLight, p: Vec3) -> f32 {
+    let to_light = light.position - p; let d_light = to_light.magnitude();
+    if d_light < 1e-6 { return 1.0; }
+    let dir = to_light / d_light;
+    let a = (light.radius / d_light).min(1.0).asin().max(1e-4);
+    let mut t = 1.0;
+    for o in occluders {
+        match *o {
+            Occluder::Sphere { center, radius } => {
+                let to_o = center - p; let d = to_o.magnitude();
+                // Skip the body the point lies on and anything not between it and the star
+                if d <= radius * 1.01 || d >= d_light || to_o.dot(&dir) <= 0.0 { continue; }
+                let b = (radius / d).min(1.0).asin();
+                let theta = (to_o.dot(&dir) / d).clamp(-1.0, 1.0).acos();
+                if theta >= a + b { continue; }
+                let full = ((b / a) * (b / a)).min(1.0);
+                let inner = (a - b).abs();
+                let cover = if theta <= inner { full } else { let s = (a + b - theta) / (a + b - inner); full * s * s * (3.0 - 2.0 * s) };
+                t *= 1.0 - cover;
+            }
+            Occluder::Ring { inv_model, ring } => {
+                let to_local = |v: Vec3| { let l = inv_model * Vec4::new(v.x, v.y, v.z, 1.0); Vec3::new(l.x, l.y, l.z) };
+                let (pl, ll) = (to_local(p), to_local(light.position));
+                // The ring's own fragments sit on the plane; with the star close to edge-on, rounding would
+                // otherwise put the crossing a hair in front of them
+                if pl.y.abs() < 1e-3 { continue; }
+                let dy = ll.y - pl.y;
+                if dy.abs() < 1e-6 { continue; }
+                let s = -pl.y / dy;
+                if s <= 0.0 || s >= 1.0 { continue; }
+                let hit = pl + (ll - pl) * s;
+                let (r, _) = ring_radius(hit, &ring);
+                if r < ring.inner || r > ring.outer { continue; }
+                t *= 1.0 - ring_opacity(hit, &ring);
+            }
+        }
+        if t <= 0.0 { return 0.0; }
+    }
+    t
+}
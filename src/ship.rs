@@ -0,0 +1,127 @@
+use crate::{camera::rotate_around_axis, input::Input};
+use minifb::Key;
+use nalgebra_glm::{Vec3, vec3};
+
+pub const ASTEROID_MATCH_VENUS_SCALE: f32 = 1.90;
+
+pub struct Lcg(u64);
+impl Lcg { pub fn new(seed: u64) -> Self { Self(seed) } pub fn next_u32(&mut self) -> u32 { self.0 = self.0.wrapping_mul(6364136223846793005).wrapping_add(1); (self.0 >> 32) as u32 } pub fn next_f32(&mut self) -> f32 { (self.next_u32() as f32) / (u32::MAX as f32) } }
+
+#[derive(Clone)]
+pub struct Ship {
+    pub pos: Vec3,
+    pub yaw: f32,
+    pub pitch: f32,
+    pub roll: f32,
+    pub vel: Vec3,
+    pub yaw_vel: f32,
+    pub roll_vel: f32,
+}
+
+// `prev_pos`/`prev_rot_y` hold the state before the last physics tick, for interpolated rendering
+pub struct Asteroid { pub pos: Vec3, pub prev_pos: Vec3, pub scale: f32, pub rot_y: f32, pub prev_rot_y: f32, pub vel: Vec3, pub alive: bool, pub exploding: bool, pub t: f32 }
+impl Asteroid {
+    pub fn new(pos: Vec3, scale: f32, rot_y: f32, vel: Vec3) -> Self { Self { pos, prev_pos: pos, scale, rot_y, prev_rot_y: rot_y, vel, alive: true, exploding: false, t: 0.0 } }
+}
+
+// Spawn an asteroid that will cross the player's view near the ship
+pub fn spawn_asteroid_crossing_ship(ship: &Ship, rng: &mut Lcg) -> Asteroid {
+    let (fwd, right, up) = ship.axes();
+    // Spawn ahead of the ship, with lateral offset so it crosses the screen
+    let ahead = 30.0 + rng.next_f32() * 20.0; // 30..50 units ahead (más cerca y visible)
+    let off_x = (rng.next_f32() - 0.5) * 30.0; 
+    let off_y = (rng.next_f32() - 0.5) * 4.0; 
+    let pos = ship.pos + fwd * ahead + right * off_x + up * off_y;
+    // Mismo tamaño que Venus
+    let scale = ASTEROID_MATCH_VENUS_SCALE;
+    let rot_y = rng.next_f32() * std::f32::consts::TAU;
+    // Velocity toward the ship (so it passes by), with slight drift
+    // Slightly slower to remain on screen longer
+    let speed = 0.08 + rng.next_f32() * 0.06; // 0.08..0.14
+    let drift_r = (rng.next_f32() - 0.5) * 0.025; // small sideways drift
+    let drift_u = (rng.next_f32() - 0.5) * 0.015; // small vertical drift
+    let vel = (-fwd * speed) + right * drift_r + up * drift_u;
+    Asteroid::new(pos, scale, rot_y, vel)
+}
+
+impl Ship {
+    pub fn new(pos: Vec3) -> Self { Self { pos, yaw: 0.0, pitch: 0.0, roll: 0.0, vel: vec3(0.0,0.0,0.0), yaw_vel: 0.0, roll_vel: 0.0 } }
+    pub fn axes(&self) -> (Vec3, Vec3, Vec3) {
+        let cp = self.pitch.cos();
+        // Forward based on yaw/pitch
+        let forward = vec3(self.yaw.cos()*cp, self.pitch.sin(), self.yaw.sin()*cp).normalize();
+        let world_up = vec3(0.0,1.0,0.0);
+        // Stable right even if forward ~ world_up
+        let mut right = forward.cross(&world_up);
+        if right.magnitude() < 1e-3 { right = forward.cross(&vec3(0.0,0.0,1.0)); }
+        let right = right.normalize();
+        let up = right.cross(&forward).normalize();
+        // Apply roll: rotate right/up around forward by roll
+        let right = rotate_around_axis(right, forward, self.roll);
+        let up = rotate_around_axis(up, forward, self.roll);
+        (forward, right, up)
+    }
+    // Pose between the previous tick (`self`) and `next`, for rendering between fixed steps
+    pub fn interpolate(&self, next: &Ship, alpha: f32) -> Ship {
+        use std::f32::consts::{PI, TAU};
+        // Shortest way around, since yaw wraps at TAU
+        let lerp_angle = |a: f32, b: f32| a + ((b - a + PI).rem_euclid(TAU) - PI) * alpha;
+        Ship { pos: self.pos.lerp(&next.pos, alpha), yaw: lerp_angle(self.yaw, next.yaw), pitch: lerp_angle(self.pitch, next.pitch), roll: lerp_angle(self.roll, next.roll), ..next.clone() }
+    }
+    // One fixed physics tick (`clock::FIXED_DT`); the constants below are per tick
+    pub fn update_controls(&mut self, input: &Input) {
+        let (forward, right, up_axis) = self.axes();
+        let mut acc = vec3(0.0,0.0,0.0);
+        // Thrust forward/back
+        if input.is_key_down(Key::W) { acc += forward * 0.02; }
+        if input.is_key_down(Key::S) { acc -= forward * 0.02; }
+        // Strafe left/right
+        if input.is_key_down(Key::D) { acc += right * 0.015; }
+        if input.is_key_down(Key::A) { acc -= right * 0.015; }
+        // Up/Down
+        if input.is_key_down(Key::R) { acc += up_axis * 0.015; }
+        if input.is_key_down(Key::F) { acc -= up_axis * 0.015; }
+        // Yaw smoothing and banking with arrows
+    let yaw_accel = 0.0028; // softer lateral acceleration
+        let mut yaw_acc = 0.0;
+        if input.is_key_down(Key::Left)  { yaw_acc -= yaw_accel; }
+        if input.is_key_down(Key::Right) { yaw_acc += yaw_accel; }
+        // Integrate yaw velocity with damping
+    self.yaw_vel = self.yaw_vel * 0.94 + yaw_acc; // a bit more damping
+    let max_yaw_vel = 0.028; // lower cap for smoother turns
+        if self.yaw_vel > max_yaw_vel { self.yaw_vel = max_yaw_vel; }
+        if self.yaw_vel < -max_yaw_vel { self.yaw_vel = -max_yaw_vel; }
+        self.yaw = (self.yaw + self.yaw_vel) % (std::f32::consts::TAU);
+        // Auto-bank proportional to yaw rate
+    let bank_target = (-self.yaw_vel * 9.5).clamp(-0.6, 0.6); // slightly softer banking
+    let bank_resp = 0.06; let bank_damp = 0.90; // smoother response and damping
+        self.roll_vel += (bank_target - self.roll) * bank_resp;
+        self.roll_vel *= bank_damp;
+        self.roll += self.roll_vel;
+    // Pitch with arrows: Up increases pitch (nose up), Down decreases
+    if input.is_key_down(Key::Up)    { self.pitch = (self.pitch + 0.015).clamp(-1.2, 1.2); }
+    if input.is_key_down(Key::Down)  { self.pitch = (self.pitch - 0.015).clamp(-1.2, 1.2); }
+        // Boost
+        if input.is_key_down(Key::LeftShift) { acc *= 2.0; }
+        self.vel += acc;
+        // Damp to avoid runaway speeds
+        self.vel *= 0.992;
+       
+        let speed = self.vel.magnitude();
+        let max_speed = 1.2;
+        if speed > max_speed { self.vel = self.vel / speed * max_speed; }
+        self.pos += self.vel;
+    }
+}
+
+pub fn clamp_ship_sphere(ship: &mut Ship, center: Vec3, radius: f32, margin: f32) {
+    let to_center = ship.pos - center;
+    let dist = to_center.magnitude();
+    let min_dist = radius + margin;
+    if dist < min_dist && dist > 1e-4 {
+        let n = to_center / dist;
+        ship.pos = center + n * min_dist;
+        let inward = -n.dot(&ship.vel).max(0.0);
+        ship.vel += n * inward;
+    }
+}
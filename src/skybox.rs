@@ -1,20 +1,169 @@
-use crate::{framebuffer::Framebuffer, color::Color};
+use crate::{clipping::to_screen, color::Color, cubemap::Cubemap, framebuffer::Framebuffer, galaxy::{self, MilkyWayDesc}, renderer::draw_polyline_clip};
+use nalgebra_glm::{Mat4, Vec3, Vec4, vec3};
+use serde::Deserialize;
+use std::collections::BTreeMap;
 
-pub struct Star { pub x: i32, pub y: i32, pub c: u32 }
+// Obliquity of the ecliptic at J2000, to turn catalog (equatorial) positions into the ecliptic world frame
+const OBLIQUITY_DEG: f32 = 23.4393;
+// Optional synthetic fill below the catalog: `FAINT_COUNT` stars at random positions between these magnitudes (about
+// the naked-eye limit). They are invented, not real stars, so they're off unless asked for
+const FAINT_COUNT: usize = 8000;
+const FAINT_MAGS: (f32, f32) = (3.8, 6.5);
+const LINE_COLOR: (u8, u8, u8) = (40, 60, 110);
 
-pub struct Skybox { stars: Vec<Star>, w: usize, h: usize }
+// Sky file: stars as [name, RA (hours), Dec (degrees), V magnitude, B-V], stick figures as chains of names and
+// the optional Milky Way background
+#[derive(Deserialize)]
+struct CatalogDesc {
+    stars: Vec<(String, f32, f32, f32, f32)>,
+    #[serde(default)] constellations: BTreeMap<String, Vec<String>>,
+    #[serde(default)] milky_way: Option<MilkyWayDesc>,
+}
+
+// A star on the celestial sphere: unit direction from the observer, linear color scaled by its brightness,
+// and the width in pixels of its image
+pub struct Star { pub dir: Vec3, pub c: Vec3, pub sigma: f32 }
+
+impl Star {
+    // Brighter stars are drawn both brighter and wider, on a compressed magnitude scale (0.3 instead of 0.4
+    // per magnitude) so the faintest still show next to Sirius without saturating the bright ones
+    fn new(dir: Vec3, mag: f32, bv: f32) -> Self {
+        Self { dir, c: bv_color(bv) * 5.0 * 10f32.powf(-0.3 * mag), sigma: 0.5 + 0.35 * (2.5 - mag).max(0.0) }
+    }
+}
+
+// Blackbody-ish color for a B-V index (blue-white O/B stars to orange-red M), normalized to a peak channel of 1
+fn bv_color(bv: f32) -> Vec3 {
+    const TABLE: [(f32, (u8, u8, u8)); 7] = [(-0.4, (155, 176, 255)), (0.0, (202, 215, 255)), (0.4, (248, 247, 255)), (0.6, (255, 244, 234)),
+        (1.0, (255, 222, 180)), (1.5, (255, 196, 132)), (2.0, (255, 170, 100))];
+    let bv = bv.clamp(TABLE[0].0, TABLE[TABLE.len() - 1].0);
+    let i = TABLE.windows(2).position(|w| bv <= w[1].0).unwrap_or(TABLE.len() - 2);
+    let ((b0, c0), (b1, c1)) = (TABLE[i], TABLE[i + 1]);
+    let (c0, c1) = (Color::new(c0.0, c0.1, c0.2).to_linear(), Color::new(c1.0, c1.1, c1.2).to_linear());
+    let c = c0.lerp(&c1, (bv - b0) / (b1 - b0));
+    c / c.max()
+}
+
+// Right ascension (hours) and declination (degrees) to a world direction: equatorial -> ecliptic (x, y, z-north)
+// -> world (x, z, -y) as in orbit.rs
+pub fn equatorial_to_world(ra_hours: f32, dec_deg: f32) -> Vec3 {
+    let (ra, dec, eps) = ((ra_hours * 15.0).to_radians(), dec_deg.to_radians(), OBLIQUITY_DEG.to_radians());
+    let (x, y, z) = (dec.cos() * ra.cos(), dec.cos() * ra.sin(), dec.sin());
+    let (ye, ze) = (y * eps.cos() + z * eps.sin(), -y * eps.sin() + z * eps.cos());
+    vec3(x, ze, -ye)
+}
+
+// Background at infinite distance: only the camera's rotation matters, so turning the ship or warping the
+// camera pans the sky while moving through the system never does. `background` is the baked Milky Way, if any
+pub struct Skybox { stars: Vec<Star>, lines: Vec<Vec<Vec3>>, background: Option<Cubemap>, pub show_lines: bool }
 
 impl Skybox {
-    pub fn new(w: usize, h: usize, count: usize, seed: u64) -> Self {
-        let mut rng = fastrand::Rng::with_seed(seed);
-        let mut stars = Vec::with_capacity(count);
-        for _ in 0..count { let x = rng.i32(0..w as i32); let y = rng.i32(0..h as i32); let g = rng.u8(180..255); let c = Color::new(g,g,g).to_hex(); stars.push(Star { x, y, c }); }
-        Self { stars, w, h }
+    // Catalog stars at their real positions, plus synthetic faint ones down to the naked-eye limit when `synthetic_seed`
+    // is given; the Milky Way, when the file has one, is baked here once
+    pub fn load(path: &str, synthetic_seed: Option<u64>) -> Result<Self, Box<dyn std::error::Error>> {
+        let text = std::fs::read_to_string(path).map_err(|e| format!("{path}: {e}"))?;
+        let desc: CatalogDesc = toml::from_str(&text).map_err(|e| format!("{path}: {e}"))?;
+        let mut stars = Vec::with_capacity(desc.stars.len() + if synthetic_seed.is_some() { FAINT_COUNT } else { 0 });
+        let mut by_name = BTreeMap::new();
+        for (name, ra, dec, mag, bv) in &desc.stars {
+            if !(0.0..24.0).contains(ra) || !(-90.0..=90.0).contains(dec) { return Err(format!("{path}: star '{name}' has RA/Dec out of range").into()); }
+            let dir = equatorial_to_world(*ra, *dec);
+            by_name.insert(name.as_str(), dir);
+            stars.push(Star::new(dir, *mag, *bv));
+        }
+        // Synthetic fill: uniform over the sphere, with magnitudes following the real counts (about 10^0.45 more stars
+        // per magnitude) so the density looks right even though no position is real
+        if let Some(seed) = synthetic_seed {
+            let mut rng = fastrand::Rng::with_seed(seed);
+            let (m0, m1) = FAINT_MAGS; let k = 0.45 * std::f32::consts::LN_10;
+            for _ in 0..FAINT_COUNT {
+                let z = rng.f32() * 2.0 - 1.0; let phi = rng.f32() * std::f32::consts::TAU; let r = (1.0 - z * z).sqrt();
+                let mag = m0 + (1.0 + rng.f32() * ((k * (m1 - m0)).exp() - 1.0)).ln() / k;
+                stars.push(Star::new(vec3(r * phi.cos(), z, r * phi.sin()), mag, rng.f32() * 1.6 - 0.2));
+            }
+        }
+        // Each chain becomes a polyline along the great circles between its stars, split every couple of degrees
+        let mut lines = Vec::new();
+        for (constellation, chains) in &desc.constellations {
+            for chain in chains {
+                let dirs = chain.split(" - ").map(|n| by_name.get(n.trim()).copied().ok_or_else(|| format!("{path}: {constellation}: unknown star '{}'", n.trim())))
+                    .collect::<Result<Vec<Vec3>, String>>()?;
+                let mut points = vec![dirs[0]];
+                for w in dirs.windows(2) {
+                    let n = (w[0].angle(&w[1]).to_degrees() / 2.0).ceil().max(1.0) as usize;
+                    points.extend((1..=n).map(|i| w[0].lerp(&w[1], i as f32 / n as f32).normalize()));
+                }
+                lines.push(points);
+            }
+        }
+        let background = desc.milky_way.as_ref().map(galaxy::bake).transpose().map_err(|e| format!("{path}: {e}"))?;
+        Ok(Self { stars, lines, background, show_lines: false })
     }
-    pub fn render(&self, fb: &mut Framebuffer) {
-        // gradient background
-        for y in 0..self.h { let t = y as f32 / (self.h as f32 - 1.0); let c = Color::from_float(0.02*(1.0-t)+0.0*t, 0.02*(1.0-t)+0.0*t, 0.05*(1.0-t)+0.0*t).to_hex(); fb.set_current_color(c); for x in 0..self.w { fb.point_no_depth(x as i32, y as i32); } }
-        // stars
-        for s in &self.stars { fb.set_current_color(s.c); fb.point_no_depth(s.x, s.y); }
+
+    // Rotation-only view-projection: the view matrix with its translation dropped
+    fn sky_matrix(view: &Mat4, proj: &Mat4) -> Mat4 {
+        let mut rot = *view; rot[(0, 3)] = 0.0; rot[(1, 3)] = 0.0; rot[(2, 3)] = 0.0;
+        proj * rot
+    }
+
+    pub fn render(&self, fb: &mut Framebuffer, view: &Mat4, proj: &Mat4, viewport: &Mat4) {
+        let m = Self::sky_matrix(view, proj);
+        let Some(inv) = m.try_inverse() else { return };
+        // Gradient from the ecliptic south pole (black) to the north (dark blue) plus the Milky Way, from each view ray
+        let (w, h) = (fb.width, fb.height);
+        let sky = |r: Vec3| {
+            let t = (r.y / r.magnitude() + 1.0) * 0.5;
+            let c = Color::from_float(0.02 * t, 0.02 * t, 0.05 * t);
+            let c = match &self.background { Some(bg) => Color::from_linear(c.to_linear() + bg.sample(r)), None => c };
+            vec3(c.r as f32, c.g as f32, c.b as f32)
+        };
+        // The unnormalized ray is linear in the pixel coordinates, so it's stepped along each row. The sky is smooth at
+        // pixel scale, so it's evaluated every SKY_STEP pixels and interpolated in between
+        const SKY_STEP: usize = 4;
+        let ray = |x: f32, y: f32| { let p = inv * Vec4::new(x / w as f32 * 2.0 - 1.0, 1.0 - y / h as f32 * 2.0, 1.0, 1.0); vec3(p.x, p.y, p.z) * p.w.signum() };
+        let step = ray(1.5, 0.5) - ray(0.5, 0.5);
+        for y in 0..h {
+            let row = ray(0.5, y as f32 + 0.5);
+            let mut next = sky(row);
+            for x0 in (0..w).step_by(SKY_STEP) {
+                let x1 = (x0 + SKY_STEP).min(w - 1);
+                let a = next; next = sky(row + step * x1 as f32);
+                for x in x0..(x0 + SKY_STEP).min(w) {
+                    let c = a.lerp(&next, (x - x0) as f32 / (x1 - x0).max(1) as f32);
+                    fb.set_current_color(Color::new((c.x + 0.5) as u8, (c.y + 0.5) as u8, (c.z + 0.5) as u8).to_hex());
+                    fb.point_no_depth(x as i32, y as i32);
+                }
+            }
+        }
+        // Constellation lines sit just inside the far plane; with z a fixed fraction of w the depth clip reduces to w > 0
+        if self.show_lines {
+            let c = Color::new(LINE_COLOR.0, LINE_COLOR.1, LINE_COLOR.2);
+            for line in &self.lines {
+                let clip: Vec<Vec4> = line.iter().map(|d| { let mut p = m * Vec4::new(d.x, d.y, d.z, 0.0); p.z = p.w * 0.999; p }).collect();
+                draw_polyline_clip(fb, viewport, &clip, 1.0, |_| c);
+            }
+        }
+        // Each star is a small gaussian normalized over the pixels it covers, so its total light doesn't change
+        // as it glides across pixel boundaries while the view turns
+        let mut weights = [0.0f32; 169];
+        for s in &self.stars {
+            let clip = m * Vec4::new(s.dir.x, s.dir.y, s.dir.z, 0.0);
+            if clip.w <= 1e-6 { continue; }
+            let p = to_screen(&clip, viewport);
+            let r = ((3.0 * s.sigma).ceil() as i32).min(6);
+            let (cx, cy) = (p.x.floor() as i32, p.y.floor() as i32);
+            if cx + r < 0 || cy + r < 0 || cx - r >= w as i32 || cy - r >= h as i32 { continue; }
+            let mut total = 0.0;
+            for (i, (dx, dy)) in (-r..=r).flat_map(|dy| (-r..=r).map(move |dx| (dx, dy))).enumerate() {
+                let (ox, oy) = ((cx + dx) as f32 + 0.5 - p.x, (cy + dy) as f32 + 0.5 - p.y);
+                weights[i] = (-(ox * ox + oy * oy) / (2.0 * s.sigma * s.sigma)).exp();
+                total += weights[i];
+            }
+            for (i, (dx, dy)) in (-r..=r).flat_map(|dy| (-r..=r).map(move |dx| (dx, dy))).enumerate() {
+                let (x, y) = (cx + dx, cy + dy);
+                if x < 0 || y < 0 || x as usize >= w || y as usize >= h { continue; }
+                fb.add_radiance(y as usize * w + x as usize, s.c * (weights[i] / total));
+            }
+        }
     }
 }
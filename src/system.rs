@@ -0,0 +1,214 @@
+use crate::{color::Color, ephemeris::MeanElements, light::PointLight, orbit::Orbit, scene::create_noise_fbmn, texture::{Filter, Mapping, Texture, Wrap}};
+use fastnoise_lite::FastNoiseLite;
+use nalgebra_glm::{Vec3, vec3};
+use serde::Deserialize;
+
+// Which fragment function a body is drawn with (see `shaders::fragment_body`)
+#[derive(Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ShaderKind { Star, Mercury, Venus, Earth, Mars, Jupiter, Saturn, Uranus, Neptune, Moon, Rocky, Gas, Asteroid, Solid, Textured }
+
+impl ShaderKind {
+    // Number of noise layers the shader indexes unconditionally
+    pub fn required_noises(self) -> usize {
+        match self {
+            ShaderKind::Star => 1,
+            ShaderKind::Venus => 4,
+            ShaderKind::Mercury | ShaderKind::Earth | ShaderKind::Mars | ShaderKind::Jupiter | ShaderKind::Saturn | ShaderKind::Uranus | ShaderKind::Neptune | ShaderKind::Rocky | ShaderKind::Gas => 2,
+            ShaderKind::Moon | ShaderKind::Asteroid | ShaderKind::Solid | ShaderKind::Textured => 0,
+        }
+    }
+}
+
+#[derive(Deserialize, Clone, Copy, Debug)]
+pub struct NoiseDesc { pub seed: i32, pub frequency: f32, pub octaves: i32 }
+
+// Keplerian elements around the parent body (or the system origin). `semi_major_axis` is in AU,
+// angles are in degrees and `speed` is the mean motion in radians per simulated second.
+#[derive(Deserialize, Clone, Copy, Debug)]
+pub struct OrbitDesc {
+    #[serde(alias = "radius")] pub semi_major_axis: f32,
+    pub speed: f32,
+    #[serde(default)] pub eccentricity: f32,
+    #[serde(default)] pub inclination: f32,
+    #[serde(default)] pub ascending_node: f32,
+    #[serde(default)] pub arg_periapsis: f32,
+    #[serde(default, alias = "phase")] pub mean_anomaly: f32,
+}
+
+impl OrbitDesc {
+    pub fn to_orbit(self, au_scale: f32) -> Orbit {
+        Orbit {
+            semi_major_axis: self.semi_major_axis * au_scale, eccentricity: self.eccentricity.clamp(0.0, 0.99),
+            inclination: self.inclination.to_radians(), ascending_node: self.ascending_node.to_radians(), arg_periapsis: self.arg_periapsis.to_radians(),
+            mean_anomaly: self.mean_anomaly.to_radians(), mean_motion: self.speed,
+        }
+    }
+}
+
+// Ring radii are multiples of the body's scale; `ellipse` stretches the ring along z
+#[derive(Deserialize, Clone, Copy, Debug)]
+pub struct RingDesc { pub inner: f32, pub outer: f32, #[serde(default = "default_ellipse")] pub ellipse: f32 }
+
+// Bitmap for the `textured` shader; the path is relative to the working directory like the OBJ paths
+#[derive(Deserialize, Clone, Debug)]
+pub struct TextureDesc { pub path: String, #[serde(default)] pub filter: Filter, #[serde(default)] pub wrap: Wrap, #[serde(default)] pub mapping: Mapping }
+
+// Light given off by a star (see `light::PointLight`)
+#[derive(Deserialize, Clone, Copy, Debug)]
+pub struct LightDesc { #[serde(default = "default_light_color")] pub color: [f32; 3], #[serde(default = "default_intensity")] pub intensity: f32, #[serde(default)] pub falloff: f32 }
+
+impl Default for LightDesc {
+    fn default() -> Self { Self { color: default_light_color(), intensity: default_intensity(), falloff: 0.0 } }
+}
+
+// Scattering shell around a body. `color` is the Rayleigh scattering color (it is also what the light loses on
+// long paths, so a blue sky gives a red terminator), `thickness` the shell height as a fraction of the radius,
+// `density` scales the scattering, and `mie`/`mie_g` set the strength and forward bias of the haze
+#[derive(Deserialize, Clone, Copy, Debug)]
+pub struct AtmosphereDesc {
+    pub color: [f32; 3],
+    #[serde(default = "default_thickness")] pub thickness: f32,
+    #[serde(default = "default_intensity")] pub density: f32,
+    #[serde(default)] pub mie: f32,
+    #[serde(default = "default_mie_g")] pub mie_g: f32,
+}
+
+// Translucent cloud layer: a noise field (sampled like the surface noises) thresholded at `coverage`, drawn on a
+// shell `height` above the surface that turns `drift` faster than the body
+#[derive(Deserialize, Clone, Copy, Debug)]
+pub struct CloudsDesc {
+    pub seed: i32,
+    #[serde(default = "default_cloud_frequency")] pub frequency: f32,
+    #[serde(default = "default_cloud_octaves")] pub octaves: i32,
+    #[serde(default = "default_coverage")] pub coverage: f32,
+    #[serde(default = "default_opacity")] pub opacity: f32,
+    #[serde(default = "default_cloud_height")] pub height: f32,
+    #[serde(default = "default_drift")] pub drift: f32,
+}
+
+pub struct Clouds { pub noise: FastNoiseLite, pub coverage: f32, pub opacity: f32, pub height: f32, pub drift: f32 }
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct BodyDesc {
+    pub name: String,
+    #[serde(default)] pub parent: Option<String>,
+    pub shader: ShaderKind,
+    pub scale: f32,
+    pub color: [f32; 3],
+    #[serde(default)] pub orbit: Option<OrbitDesc>,
+    #[serde(default = "default_spin")] pub spin: f32,
+    #[serde(default)] pub noises: Vec<NoiseDesc>,
+    #[serde(default)] pub rings: Option<RingDesc>,
+    #[serde(default)] pub ephemeris: Option<MeanElements>,
+    #[serde(default)] pub texture: Option<TextureDesc>,
+    // Light the surface gives off on top of its color; feeds the bloom pass. Stars default to 3
+    #[serde(default)] pub emission: Option<f32>,
+    // Stars light the rest of the system; the first body with a light is the one used
+    #[serde(default)] pub light: Option<LightDesc>,
+    #[serde(default)] pub atmosphere: Option<AtmosphereDesc>,
+    #[serde(default)] pub clouds: Option<CloudsDesc>,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct SystemDesc { #[serde(default = "default_au_scale")] pub au_scale: f32, pub bodies: Vec<BodyDesc> }
+
+fn default_ellipse() -> f32 { 1.0 }
+fn default_spin() -> f32 { 0.3 }
+fn default_au_scale() -> f32 { 10.0 }
+fn default_light_color() -> [f32; 3] { [1.0, 1.0, 1.0] }
+fn default_intensity() -> f32 { 1.0 }
+fn default_thickness() -> f32 { 0.08 }
+fn default_mie_g() -> f32 { 0.76 }
+fn default_cloud_frequency() -> f32 { 0.9 }
+fn default_cloud_octaves() -> i32 { 5 }
+fn default_coverage() -> f32 { 0.55 }
+fn default_opacity() -> f32 { 0.85 }
+fn default_cloud_height() -> f32 { 0.02 }
+fn default_drift() -> f32 { 0.05 }
+
+pub struct Body {
+    pub name: String,
+    pub parent: Option<usize>,
+    pub shader: ShaderKind,
+    pub scale: f32,
+    pub color: Color,
+    pub orbit: Option<Orbit>,
+    pub spin: f32,
+    pub noises: Vec<FastNoiseLite>,
+    pub rings: Option<RingDesc>,
+    pub ephemeris: Option<MeanElements>,
+    pub texture: Option<Texture>,
+    pub emission: f32,
+    pub light: Option<LightDesc>,
+    pub atmosphere: Option<AtmosphereDesc>,
+    pub clouds: Option<Clouds>,
+}
+
+// Simulation time: `tsec` drives the abstract orbits; when `jd` is set, bodies with mean elements
+// are placed where they are on that Julian day
+#[derive(Clone, Copy, Debug)]
+pub struct SimTime { pub tsec: f64, pub jd: Option<f64> }
+
+impl Body {
+    // Orbit in effect at `t` and the current mean anomaly along it
+    pub fn orbit_at(&self, t: SimTime) -> Option<(Orbit, f32)> {
+        let orbit = self.orbit?;
+        match (self.ephemeris, t.jd) {
+            (Some(el), Some(jd)) => { let o = el.orbit_at(jd, orbit.semi_major_axis); Some((o, o.mean_anomaly)) }
+            _ => Some((orbit, orbit.mean_anomaly_at(t.tsec))),
+        }
+    }
+}
+
+pub struct SolarSystem { pub bodies: Vec<Body> }
+
+impl SolarSystem {
+    pub fn load(path: &str) -> Result<Self, Box<dyn std::error::Error>> {
+        let text = std::fs::read_to_string(path).map_err(|e| format!("{path}: {e}"))?;
+        let desc: SystemDesc = toml::from_str(&text).map_err(|e| format!("{path}: {e}"))?;
+        Self::from_desc(&desc)
+    }
+
+    pub fn from_desc(desc: &SystemDesc) -> Result<Self, Box<dyn std::error::Error>> {
+        let mut bodies: Vec<Body> = Vec::with_capacity(desc.bodies.len());
+        for b in &desc.bodies {
+            // Parents must be declared before their satellites
+            let parent = match &b.parent {
+                Some(p) => Some(bodies.iter().position(|o| &o.name == p).ok_or_else(|| format!("body {}: unknown parent {p}", b.name))?),
+                None => None,
+            };
+            if b.noises.len() < b.shader.required_noises() { return Err(format!("body {}: shader {:?} needs at least {} noise layers", b.name, b.shader, b.shader.required_noises()).into()); }
+            if b.ephemeris.is_some() && b.orbit.is_none() { return Err(format!("body {}: ephemeris needs an orbit for the display semi-major axis", b.name).into()); }
+            if b.shader == ShaderKind::Textured && b.texture.is_none() { return Err(format!("body {}: shader textured needs a texture", b.name).into()); }
+            let texture = match &b.texture { Some(t) => Some(Texture::load(&t.path, t.filter, t.wrap, t.mapping).map_err(|e| format!("body {}: {e}", b.name))?), None => None };
+            let light = b.light.or((b.shader == ShaderKind::Star).then(LightDesc::default));
+            let emission = b.emission.unwrap_or(if b.shader == ShaderKind::Star { 3.0 } else { 0.0 });
+            let orbit = b.orbit.map(|o| o.to_orbit(desc.au_scale));
+            let clouds = b.clouds.map(|c| Clouds { noise: create_noise_fbmn(c.seed, c.frequency, c.octaves), coverage: c.coverage, opacity: c.opacity, height: c.height, drift: c.drift });
+            let noises = b.noises.iter().map(|n| create_noise_fbmn(n.seed, n.frequency, n.octaves)).collect();
+            bodies.push(Body { name: b.name.clone(), parent, shader: b.shader, scale: b.scale, color: Color::from_float(b.color[0], b.color[1], b.color[2]), orbit, spin: b.spin, noises, rings: b.rings, ephemeris: b.ephemeris, texture, emission, light, atmosphere: b.atmosphere, clouds });
+        }
+        Ok(Self { bodies })
+    }
+
+    // World-space position of body `i` at `t`, following the parent chain
+    pub fn position(&self, i: usize, t: SimTime) -> Vec3 {
+        let body = &self.bodies[i];
+        let origin = body.parent.map(|p| self.position(p, t)).unwrap_or(vec3(0.0, 0.0, 0.0));
+        match body.orbit_at(t) {
+            Some((o, m)) => origin + o.position_at_mean_anomaly(m),
+            None => origin,
+        }
+    }
+
+    // Light from the first star at `t`; a system without one is lit from the origin.
+    // Bodies are unit-diameter spheres scaled by `scale`, so the radius is half of it
+    pub fn light(&self, t: SimTime) -> PointLight {
+        let Some((i, l)) = self.bodies.iter().enumerate().find_map(|(i, b)| b.light.map(|l| (i, l))) else { return PointLight::default() };
+        PointLight { position: self.position(i, t), color: vec3(l.color[0], l.color[1], l.color[2]), intensity: l.intensity, falloff: l.falloff, radius: self.bodies[i].scale * 0.5 }
+    }
+
+    // Bodies orbiting the system origin (the ones selectable with the number keys)
+    pub fn primaries(&self) -> impl Iterator<Item = usize> + '_ { self.bodies.iter().enumerate().filter(|(_, b)| b.parent.is_none()).map(|(i, _)| i) }
+}
@@ -0,0 +1,74 @@
+use crate::color::Color;
+use nalgebra_glm::{Vec2, Vec3, vec2};
+use serde::Deserialize;
+
+#[derive(Deserialize, Clone, Copy, Debug, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum Filter { Nearest, #[default] Bilinear }
+
+// What happens to UVs outside [0, 1]
+#[derive(Deserialize, Clone, Copy, Debug, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum Wrap { #[default] Repeat, Mirror, Clamp }
+
+// How fragments get their UVs: the mesh's own, or equirectangular from the object-space position
+// (planets: the bundled sphere.obj maps every triangle to the whole [0, 1] square)
+#[derive(Deserialize, Clone, Copy, Debug, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum Mapping { #[default] Uv, Spherical }
+
+// Longitude/latitude UV of a point on a sphere centered at the origin, v = 0 at the north pole (+Y)
+pub fn spherical_uv(p: Vec3) -> Vec2 {
+    let r = p.magnitude().max(1e-6);
+    vec2(0.5 + p.z.atan2(p.x) / std::f32::consts::TAU, 0.5 - (p.y / r).clamp(-1.0, 1.0).asin() / std::f32::consts::PI)
+}
+
+// RGB bitmap sampled with UVs in [0, 1]; (0, 0) is the top-left texel (obj.rs already flips v)
+pub struct Texture {
+    pub width: usize,
+    pub height: usize,
+    pub texels: Vec<Color>,
+    pub filter: Filter,
+    pub wrap: Wrap,
+    pub mapping: Mapping,
+}
+
+impl Texture {
+    pub fn load(path: &str, filter: Filter, wrap: Wrap, mapping: Mapping) -> Result<Self, Box<dyn std::error::Error>> {
+        let img = image::open(path).map_err(|e| format!("{path}: {e}"))?.to_rgb8();
+        let texels = img.pixels().map(|p| Color::new(p[0], p[1], p[2])).collect();
+        Ok(Self { width: img.width() as usize, height: img.height() as usize, texels, filter, wrap, mapping })
+    }
+
+    // Texel index along one axis after applying the wrap mode
+    fn wrap_index(&self, i: i32, n: usize) -> usize {
+        let n = n as i32;
+        match self.wrap {
+            Wrap::Repeat => i.rem_euclid(n) as usize,
+            Wrap::Clamp => i.clamp(0, n - 1) as usize,
+            Wrap::Mirror => { let m = i.rem_euclid(2 * n); (if m < n { m } else { 2 * n - 1 - m }) as usize }
+        }
+    }
+
+    fn texel(&self, x: i32, y: i32) -> Color { self.texels[self.wrap_index(y, self.height) * self.width + self.wrap_index(x, self.width)] }
+
+    pub fn sample(&self, uv: Vec2) -> Color {
+        let x = uv.x * self.width as f32; let y = uv.y * self.height as f32;
+        match self.filter {
+            Filter::Nearest => self.texel(x.floor() as i32, y.floor() as i32),
+            Filter::Bilinear => {
+                // Texel centers sit at half-integer coordinates
+                let (x, y) = (x - 0.5, y - 0.5);
+                let (x0, y0) = (x.floor(), y.floor()); let (fx, fy) = (x - x0, y - y0); let (x0, y0) = (x0 as i32, y0 as i32);
+                let top = mix(self.texel(x0, y0), self.texel(x0 + 1, y0), fx);
+                let bottom = mix(self.texel(x0, y0 + 1), self.texel(x0 + 1, y0 + 1), fx);
+                mix(top, bottom, fy)
+            }
+        }
+    }
+}
+
+fn mix(a: Color, b: Color, t: f32) -> Color {
+    let l = |a: u8, b: u8| (a as f32 + (b as f32 - a as f32) * t).round() as u8;
+    Color::new(l(a.r, b.r), l(a.g, b.g), l(a.b, b.b))
+}
@@ -0,0 +1,62 @@
+use nalgebra_glm::{Vec3, vec3};
+
+// Operator used to bring the linear HDR buffer into [0, 1] before sRGB encoding
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Tonemap {
+    // Plain clamp after exposure: matches the LDR look, highlights still clip
+    Clamp,
+    Reinhard,
+    // Narkowicz's fit of the ACES filmic curve
+    Aces,
+    // 1 - exp(-c): soft shoulder, exposure sets where it starts
+    Exposure,
+}
+
+impl Tonemap {
+    pub fn parse(s: &str) -> Result<Self, String> {
+        match s {
+            "clamp" => Ok(Tonemap::Clamp), "reinhard" => Ok(Tonemap::Reinhard), "aces" => Ok(Tonemap::Aces), "exposure" => Ok(Tonemap::Exposure),
+            _ => Err(format!("unknown tonemap {s}, expected clamp, reinhard, aces or exposure")),
+        }
+    }
+
+    pub fn apply(self, c: Vec3, exposure: f32) -> Vec3 {
+        let c = c * exposure;
+        let f = |x: f32| match self {
+            Tonemap::Clamp => x,
+            Tonemap::Reinhard => x / (1.0 + x),
+            Tonemap::Aces => (x * (2.51 * x + 0.03)) / (x * (2.43 * x + 0.59) + 0.14),
+            Tonemap::Exposure => 1.0 - (-x).exp(),
+        };
+        vec3(f(c.x), f(c.y), f(c.z)).map(|v| v.clamp(0.0, 1.0))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn operators_map_into_the_unit_range() {
+        for t in [Tonemap::Clamp, Tonemap::Reinhard, Tonemap::Aces, Tonemap::Exposure] {
+            assert!(t.apply(Vec3::zeros(), 1.0).magnitude() < 1e-2, "{t:?}");
+            let mut last = 0.0;
+            for i in 0..200 {
+                let v = t.apply(Vec3::repeat(i as f32 * 0.1), 1.0).x;
+                assert!((0.0..=1.0).contains(&v) && v >= last, "{t:?} at {}: {v}", i as f32 * 0.1);
+                last = v;
+            }
+            // Exposure scales the input before the curve
+            assert_eq!(t.apply(Vec3::repeat(0.25), 2.0), t.apply(Vec3::repeat(0.5), 1.0));
+        }
+        assert_eq!(Tonemap::Clamp.apply(vec3(0.3, 1.5, -1.0), 1.0), vec3(0.3, 1.0, 0.0));
+        assert_eq!(Tonemap::Reinhard.apply(Vec3::repeat(1.0), 1.0), Vec3::repeat(0.5));
+        assert!(Tonemap::Aces.apply(Vec3::repeat(100.0), 1.0).x > 0.99 && Tonemap::Exposure.apply(Vec3::repeat(20.0), 1.0).x > 0.99);
+    }
+
+    #[test]
+    fn parse_names() {
+        assert_eq!(Tonemap::parse("aces"), Ok(Tonemap::Aces));
+        assert!(Tonemap::parse("filmic").is_err());
+    }
+}
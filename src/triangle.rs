@@ -1,36 +1,134 @@
-use crate::{fragment::Fragment, vertex::Vertex};
+use crate::{fragment::Fragment, framebuffer::MAX_SAMPLES, vertex::Vertex};
 use nalgebra_glm::{dot, Vec2, Vec3};
 
 fn edge(a: &Vec3, b: &Vec3, c: &Vec3) -> f32 { (c.x - a.x) * (b.y - a.y) - (c.y - a.y) * (b.x - a.x) }
 
-pub fn triangle_stream<F: FnMut(&Fragment) -> ()>(v1: &Vertex, v2: &Vertex, v3: &Vertex, screen_w: usize, screen_h: usize, mut emit: F) {
+// Whether the edge u -> v of a front-facing triangle is a left edge (interior to its right) or a top edge
+// (horizontal, interior below); screen y grows downwards
+fn top_left(u: &Vec3, v: &Vec3) -> bool { let (dx, dy) = (v.x - u.x, v.y - u.y); dy > 0.0 || (dy == 0.0 && dx < 0.0) }
+
+// Pixel rectangle, inclusive on both ends
+#[derive(Clone, Copy, Debug)]
+pub struct Rect { pub x0: i32, pub y0: i32, pub x1: i32, pub y1: i32 }
+
+// Screen bounding box of a front-facing, non-degenerate triangle, clipped to `clip`
+pub fn triangle_bounds(v1: &Vertex, v2: &Vertex, v3: &Vertex, clip: &Rect) -> Option<Rect> {
     let a = v1.transformed_position; let b = v2.transformed_position; let c = v3.transformed_position;
    
-    if !a.x.is_finite() || !a.y.is_finite() || !b.x.is_finite() || !b.y.is_finite() || !c.x.is_finite() || !c.y.is_finite() { return; }
+    if !a.x.is_finite() || !a.y.is_finite() || !b.x.is_finite() || !b.y.is_finite() || !c.x.is_finite() || !c.y.is_finite() { return None; }
     let area = edge(&a,&b,&c);
-    if area.abs() < 1e-6 { return; }
+    if area.abs() < 1e-6 { return None; }
    
-    if area < 0.0 { return; }
+    if area < 0.0 { return None; }
   
-    let mut min_x = a.x.min(b.x).min(c.x).floor() as i32;
-    let mut min_y = a.y.min(b.y).min(c.y).floor() as i32;
-    let mut max_x = a.x.max(b.x).max(c.x).ceil() as i32;
-    let mut max_y = a.y.max(b.y).max(c.y).ceil() as i32;
-    if max_x < 0 || max_y < 0 || min_x as usize >= screen_w || min_y as usize >= screen_h { return; }
-    if min_x < 0 { min_x = 0; } if min_y < 0 { min_y = 0; }
-    if max_x >= screen_w as i32 { max_x = screen_w as i32 - 1; }
-    if max_y >= screen_h as i32 { max_y = screen_h as i32 - 1; }
-    if min_x > max_x || min_y > max_y { return; }
+    let min_x = (a.x.min(b.x).min(c.x).floor() as i32).max(clip.x0);
+    let min_y = (a.y.min(b.y).min(c.y).floor() as i32).max(clip.y0);
+    let max_x = (a.x.max(b.x).max(c.x).ceil() as i32).min(clip.x1);
+    let max_y = (a.y.max(b.y).max(c.y).ceil() as i32).min(clip.y1);
+    if min_x > max_x || min_y > max_y { return None; }
+    Some(Rect { x0: min_x, y0: min_y, x1: max_x, y1: max_y })
+}
+
+// Which of a pixel's samples (see `framebuffer::sample_offsets`) the triangle covers, and its depth at each
+pub struct Coverage { pub mask: u8, pub depth: [f32; MAX_SAMPLES] }
+
+// Emits the fragments of the triangle that fall inside `clip` (a tile or the whole screen). A pixel with any of
+// its `samples` inside the triangle is shaded once, at its center, or at the first covered sample when the
+// center is outside (so varyings aren't extrapolated past the edge)
+pub fn triangle_stream<F: FnMut(&Fragment, &Coverage)>(v1: &Vertex, v2: &Vertex, v3: &Vertex, clip: &Rect, samples: &[(f32, f32)], mut emit: F) {
+    let Some(Rect { x0: min_x, y0: min_y, x1: max_x, y1: max_y }) = triangle_bounds(v1, v2, v3, clip) else { return };
+    let a = v1.transformed_position; let b = v2.transformed_position; let c = v3.transformed_position;
+    let area = edge(&a,&b,&c);
     let light_dir = Vec3::new(0.0, 0.0, 1.0);
+    let bary = |px: f32, py: f32| { let p = Vec3::new(px, py, 0.0); (edge(&b, &c, &p) / area, edge(&c, &a, &p) / area, edge(&a, &b, &p) / area) };
+    // Top-left fill rule: a sample exactly on an edge belongs to the triangle only if that edge is a top or left one,
+    // so a sample on an edge shared by two triangles is covered once (and blended once)
+    let (t1, t2, t3) = (top_left(&b, &c), top_left(&c, &a), top_left(&a, &b));
+    let on = |w: f32, top_left: bool| w > 0.0 || (w == 0.0 && top_left);
+    let inside = |(w1, w2, w3): (f32, f32, f32)| on(w1, t1) && on(w2, t2) && on(w3, t3);
+    // 1/w of each vertex; clipping guarantees w > 0
+    let (iw1, iw2, iw3) = (1.0 / v1.clip_position.w, 1.0 / v2.clip_position.w, 1.0 / v3.clip_position.w);
     for y in min_y..=max_y { for x in min_x..=max_x {
-        let p = Vec3::new(x as f32 + 0.5, y as f32 + 0.5, 0.0);
-        let w1 = edge(&b, &c, &p) / area; let w2 = edge(&c, &a, &p) / area; let w3 = edge(&a, &b, &p) / area;
-        if w1 >= 0.0 && w2 >= 0.0 && w3 >= 0.0 {
-            let normal = (v1.transformed_normal * w1 + v2.transformed_normal * w2 + v3.transformed_normal * w3).normalize();
-            let intensity = dot(&normal, &light_dir).max(0.0);
-            let depth = a.z * w1 + b.z * w2 + c.z * w3;
-            let vertex_position = v1.position * w1 + v2.position * w2 + v3.position * w3;
-            emit(&Fragment::new(Vec2::new(x as f32, y as f32), depth, normal, intensity, vertex_position));
+        let mut cov = Coverage { mask: 0, depth: [0.0; MAX_SAMPLES] };
+        let mut first = None;
+        for (s, &(sx, sy)) in samples.iter().enumerate() {
+            let w = bary(x as f32 + sx, y as f32 + sy);
+            // Screen depth (z/w) is affine in screen space
+            if inside(w) { cov.mask |= 1 << s; cov.depth[s] = a.z * w.0 + b.z * w.1 + c.z * w.2; first.get_or_insert(w); }
         }
+        let Some(first) = first else { continue };
+        let (w1, w2, w3) = if samples.len() == 1 { first } else { Some(bary(x as f32 + 0.5, y as f32 + 0.5)).filter(|w| inside(*w)).unwrap_or(first) };
+        // Every other varying is interpolated as attribute/w and renormalized by the interpolated 1/w
+        let depth = a.z * w1 + b.z * w2 + c.z * w3;
+        let (p1, p2, p3) = (w1 * iw1, w2 * iw2, w3 * iw3); let inv = 1.0 / (p1 + p2 + p3);
+        let (p1, p2, p3) = (p1 * inv, p2 * inv, p3 * inv);
+        let normal = (v1.transformed_normal * p1 + v2.transformed_normal * p2 + v3.transformed_normal * p3).normalize();
+        let intensity = dot(&normal, &light_dir).max(0.0);
+        let vertex_position = v1.position * p1 + v2.position * p2 + v3.position * p3;
+        let tex_coords = v1.tex_coords * p1 + v2.tex_coords * p2 + v3.tex_coords * p3;
+        let tangent = v1.transformed_tangent * p1 + v2.transformed_tangent * p2 + v3.transformed_tangent * p3;
+        emit(&Fragment::new(Vec2::new(x as f32, y as f32), depth, normal, intensity, vertex_position, tex_coords, tangent), &cov);
     }}
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use nalgebra_glm::vec3;
+
+    // Front-facing triangle through three screen points
+    fn triangle(p: [Vec3; 3]) -> [Vertex; 3] {
+        let [a, b, c] = if edge(&p[0], &p[1], &p[2]) > 0.0 { p } else { [p[0], p[2], p[1]] };
+        [a, b, c].map(|p| Vertex::new(p, vec3(0.0, 0.0, 1.0)))
+    }
+
+    // How many of `tris` cover each sample, indexed by pixel
+    fn coverage_counts(tris: &[[Vertex; 3]], samples: &[(f32, f32)], size: i32) -> Vec<u32> {
+        let clip = Rect { x0: 0, y0: 0, x1: size - 1, y1: size - 1 };
+        let mut counts = vec![0; (size * size) as usize * samples.len()];
+        for [a, b, c] in tris {
+            triangle_stream(a, b, c, &clip, samples, |frag, cov| {
+                let px = (frag.position.y as i32 * size + frag.position.x as i32) as usize;
+                for s in 0..samples.len() { if cov.mask & (1 << s) != 0 { counts[px * samples.len() + s] += 1; } }
+            });
+        }
+        counts
+    }
+
+    #[test]
+    fn shared_diagonal_is_covered_once() {
+        // Pixel centers of x + y = 3 lie exactly on the diagonal
+        let tris = [triangle([vec3(0.0, 0.0, 0.0), vec3(0.0, 4.0, 0.0), vec3(4.0, 0.0, 0.0)]), triangle([vec3(4.0, 0.0, 0.0), vec3(0.0, 4.0, 0.0), vec3(4.0, 4.0, 0.0)])];
+        let counts = coverage_counts(&tris, &[(0.5, 0.5)], 6);
+        for y in 0..6 { for x in 0..6 { assert_eq!(counts[y * 6 + x], (x < 4 && y < 4) as u32, "pixel ({x}, {y})"); } }
+    }
+
+    #[test]
+    fn msaa_coverage_follows_the_edge() {
+        // Left of the vertical edge x = 2.5, with depth growing along x
+        let tri = triangle([vec3(2.5, -10.0, 0.25), vec3(2.5, 20.0, 0.25), vec3(-17.5, 5.0, 0.05)]);
+        let samples = crate::framebuffer::sample_offsets(4);
+        let clip = Rect { x0: 0, y0: 0, x1: 7, y1: 7 };
+        let mut seen = Vec::new();
+        triangle_stream(&tri[0], &tri[1], &tri[2], &clip, samples, |frag, cov| {
+            let x = frag.position.x as i32;
+            // Samples at x + 0.375, 0.875, 0.125 and 0.625: on the edge pixel only the first and third are inside
+            assert_eq!(cov.mask, if x == 2 { 0b0101 } else { 0b1111 }, "pixel x = {x}");
+            for s in (0..4).filter(|s| cov.mask & (1 << s) != 0) { assert!((cov.depth[s] - (0.25 + (x as f32 + samples[s].0 - 2.5) * 0.01)).abs() < 1e-5); }
+            // The center of the edge pixel is outside, so it's shaded at its first covered sample
+            if x == 2 { assert!((frag.vertex_position.x - 2.375).abs() < 1e-5); } else { assert!((frag.vertex_position.x - (x as f32 + 0.5)).abs() < 1e-5); }
+            seen.push(x);
+        });
+        assert!(seen.contains(&2) && seen.iter().all(|&x| x <= 2));
+    }
+
+    #[test]
+    fn fan_covers_every_sample_on_its_edges_once() {
+        // Four triangles around (2, 2); samples on the pixel corners hit the horizontal, vertical and diagonal edges,
+        // the shared center and the outer border, where only the top and left sides are filled
+        let (m, corners) = (vec3(2.0, 2.0, 0.0), [vec3(0.0, 0.0, 0.0), vec3(4.0, 0.0, 0.0), vec3(4.0, 4.0, 0.0), vec3(0.0, 4.0, 0.0)]);
+        let tris: Vec<_> = (0..4).map(|i| triangle([m, corners[i], corners[(i + 1) % 4]])).collect();
+        let counts = coverage_counts(&tris, &[(0.0, 0.0)], 6);
+        for y in 0..6 { for x in 0..6 { assert_eq!(counts[y * 6 + x], (x < 4 && y < 4) as u32, "sample ({x}, {y})"); } }
+    }
+}
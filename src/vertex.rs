@@ -1,13 +1,22 @@
-use nalgebra_glm::Vec3;
+use nalgebra_glm::{Vec2, Vec3, Vec4};
 use crate::color::Color;
 
 #[derive(Clone, Debug)]
 pub struct Vertex {
     pub position: Vec3,
     pub normal: Vec3,
+    pub tex_coords: Vec2,
+    // Direction of increasing u on the surface, for normal maps (zero when the mesh has no UVs)
+    pub tangent: Vec3,
+    // Position after projection, before the perspective divide (see `clipping`)
+    pub clip_position: Vec4,
     pub transformed_position: Vec3,
     pub transformed_normal: Vec3,
+    pub transformed_tangent: Vec3,
     pub color: Color,
 }
 
-impl Vertex { pub fn new(position: Vec3, normal: Vec3) -> Self { Self { position, normal, transformed_position: position, transformed_normal: normal, color: Color::new(0,0,0) } } }
+impl Vertex {
+    pub fn new(position: Vec3, normal: Vec3) -> Self { Self::with_tex_coords(position, normal, Vec2::zeros()) }
+    pub fn with_tex_coords(position: Vec3, normal: Vec3, tex_coords: Vec2) -> Self { Self { position, normal, tex_coords, tangent: Vec3::zeros(), clip_position: Vec4::new(position.x, position.y, position.z, 1.0), transformed_position: position, transformed_normal: normal, transformed_tangent: Vec3::zeros(), color: Color::new(0,0,0) } }
+}